@@ -0,0 +1,73 @@
+// Builds as part of the workspace to check that `palserializer` and the code
+// generated by its derive macros only need `core` and `alloc`. The crate is
+// `no_std` itself, so any `::std` path in the generated code fails to resolve.
+// Within the workspace `palserializer` is built with `std`; run
+// `cargo test -p no-std-check` to test the derived types against the
+// `alloc`-only build.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use palserializer::{PalsDecode, PalsEncode};
+
+#[derive(Debug, PartialEq, PalsEncode, PalsDecode)]
+pub struct Message<'a> {
+    pub id: u32,
+    pub name: String,
+    pub body: &'a [u8],
+    #[pals(skip)]
+    pub cache: Vec<u8>,
+    #[pals(nested)]
+    pub kind: Kind,
+    #[pals(default)]
+    pub retries: u8,
+}
+
+#[derive(Debug, PartialEq, Default, PalsEncode, PalsDecode)]
+pub enum Kind {
+    #[default]
+    Empty,
+    Pair(u8, u16),
+    Named { flag: bool, tags: Vec<u8> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use palserializer::{decode, deserialize_be, encode, PalsError};
+
+    fn message(body: &[u8]) -> Message<'_> {
+        Message {
+            id: 7,
+            name: "name".to_string(),
+            body,
+            cache: vec![1, 2],
+            kind: Kind::Named { flag: true, tags: vec![3, 4] },
+            retries: 2,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_struct() {
+        let encoded = encode(&message(b"body"));
+        let decoded: Message = decode(&encoded).unwrap();
+
+        assert_eq!(decoded, Message { cache: Vec::new(), ..message(b"body") });
+        assert_eq!(deserialize_be(&encoded).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_round_trip_enum() {
+        for kind in [Kind::Empty, Kind::Pair(1, 300), Kind::Named { flag: false, tags: vec![] }] {
+            assert_eq!(decode::<Kind>(&encode(&kind)).unwrap(), kind);
+        }
+
+        let encoded = encode(&Kind::Pair(1, 2));
+        assert!(matches!(decode::<Message>(&encoded), Err(PalsError::SegmentCount { .. })));
+    }
+}
@@ -0,0 +1,284 @@
+/*      Derive macros for palserializer
+    `#[derive(PalsEncode)]` and `#[derive(PalsDecode)]` implement the
+    `palserializer::Encode` and `palserializer::Decode` traits for structs and
+    enums. A struct becomes one `serialize_be` frame with a segment per field
+    in declaration order. An enum becomes a frame whose first segment is the
+    u32 index of the variant, followed by a segment per field of the variant.
+
+    Fields accept these attributes:
+        #[pals(skip)]     the field is not encoded and decodes as `Default::default()`
+        #[pals(default)]  the field may be missing from the end of the frame and
+                          then decodes as `Default::default()`
+        #[pals(nested)]   the field is itself a PALS frame, which is checked to be
+                          complete before the field is decoded
+*/
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Generics, Ident, Lifetime, LifetimeParam};
+
+#[proc_macro_derive(PalsEncode, attributes(pals))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(PalsDecode, attributes(pals))]
+pub fn derive_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+// How a single field is mapped onto the frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Plain,
+    Skip,
+    Default,
+    Nested,
+}
+
+struct Field {
+    // The name of the field, or its index for tuple fields.
+    member: syn::Member,
+    // The name the field is bound to while matching an enum variant.
+    binding: Ident,
+    mode: Mode,
+}
+
+fn parse_fields(fields: &Fields) -> Result<Vec<Field>, Error> {
+    let mut parsed = Vec::new();
+    let mut seen_default = false;
+
+    for (index, field) in fields.iter().enumerate() {
+        let mut mode = Mode::Plain;
+
+        for attr in field.attrs.iter().filter(|i| i.path().is_ident("pals")) {
+            attr.parse_nested_meta(|meta| {
+                let next = if meta.path.is_ident("skip") {
+                    Mode::Skip
+                } else if meta.path.is_ident("default") {
+                    Mode::Default
+                } else if meta.path.is_ident("nested") {
+                    Mode::Nested
+                } else {
+                    return Err(meta.error("expected `skip`, `default` or `nested`"));
+                };
+
+                if mode != Mode::Plain {
+                    return Err(meta.error("only one of `skip`, `default` and `nested` can be used per field"));
+                }
+                mode = next;
+                Ok(())
+            })?;
+        }
+
+        // Missing fields can only be detected at the end of the frame, so once
+        // a field is optional every encoded field after it has to be as well.
+        match mode {
+            Mode::Default => seen_default = true,
+            Mode::Plain | Mode::Nested if seen_default => {
+                return Err(Error::new_spanned(field, "fields after a `#[pals(default)]` field must be `default` too"));
+            }
+            _ => {}
+        }
+
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(index.into()),
+        };
+
+        parsed.push(Field { member, binding: format_ident!("__field{}", index), mode });
+    }
+
+    Ok(parsed)
+}
+
+// The number of segments the encoded fields take up.
+fn segment_count(fields: &[Field]) -> usize {
+    fields.iter().filter(|i| i.mode != Mode::Skip).count()
+}
+
+// Statements pushing one segment per encoded field, each taken from `value`.
+fn encode_fields(fields: &[Field], value: impl Fn(&Field) -> TokenStream2) -> TokenStream2 {
+    let segments = fields.iter().filter(|i| i.mode != Mode::Skip).map(|i| {
+        let value = value(i);
+        quote! { let _ = __frame.segment(#value); }
+    });
+    quote! { #(#segments)* }
+}
+
+// The field initializers of a struct or variant literal read from `__frame`.
+fn decode_fields(fields: &[Field]) -> TokenStream2 {
+    let fields = fields.iter().map(|i| {
+        let member = &i.member;
+        let value = match i.mode {
+            Mode::Plain => quote! { __frame.read()? },
+            Mode::Skip => quote! { ::core::default::Default::default() },
+            Mode::Default => quote! { __frame.read_or_default()? },
+            Mode::Nested => quote! { __frame.read_nested()? },
+        };
+        quote! { #member: #value }
+    });
+    quote! { { #(#fields),* } }
+}
+
+// A pattern binding every field of a variant to its `binding` name.
+fn bind_fields(fields: &[Field]) -> TokenStream2 {
+    let fields = fields.iter().map(|i| {
+        let member = &i.member;
+        let binding = &i.binding;
+        quote! { #member: #binding }
+    });
+    quote! { { #(#fields),* } }
+}
+
+// Require `bound` of every type parameter, so generic fields can be encoded.
+fn add_bounds(generics: &mut Generics, bound: TokenStream2) {
+    let params: Vec<_> = generics.type_params().map(|i| i.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+
+    for param in params {
+        where_clause.predicates.push(parse_quote! { #param: #bound });
+    }
+}
+
+fn expand_encode(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    add_bounds(&mut generics, quote! { ::palserializer::Encode });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = parse_fields(&data.fields)?;
+            let count = segment_count(&fields);
+            let segments = encode_fields(&fields, |i| {
+                let member = &i.member;
+                quote! { &self.#member }
+            });
+
+            quote! {
+                // The number of segments is fixed by the fields, so encoding cannot fail.
+                let mut __frame = ::palserializer::FrameEncoder::new(output, #count);
+                #segments
+                let _ = __frame.finish();
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+
+            for (index, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let index = u32::try_from(index).map_err(|_| Error::new_spanned(variant, "too many variants"))?;
+                let fields = parse_fields(&variant.fields)?;
+                let count = segment_count(&fields) + 1;
+                let pattern = bind_fields(&fields);
+                let segments = encode_fields(&fields, |i| {
+                    let binding = &i.binding;
+                    quote! { #binding }
+                });
+
+                arms.push(quote! {
+                    #[allow(unused_variables)]
+                    #name::#variant_name #pattern => {
+                        let mut __frame = ::palserializer::FrameEncoder::new(output, #count);
+                        let _ = __frame.segment(&#index);
+                        #segments
+                        let _ = __frame.finish();
+                    }
+                });
+            }
+
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(Error::new_spanned(data.union_token, "`PalsEncode` cannot be derived for unions"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::palserializer::Encode for #name #ty_generics #where_clause {
+            fn encode_to(&self, output: &mut ::palserializer::__Vec<u8>) {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_decode(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+
+    // Decoded fields may borrow from the input, so the input has to outlive
+    // every lifetime of the type.
+    let data_lifetime = Lifetime::new("'__pals", Span::call_site());
+    let mut generics = input.generics.clone();
+    let mut data_param = LifetimeParam::new(data_lifetime.clone());
+    data_param.bounds.extend(input.generics.lifetimes().map(|i| i.lifetime.clone()));
+    generics.params.insert(0, data_param.into());
+    add_bounds(&mut generics, quote! { ::palserializer::Decode<#data_lifetime> });
+
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = decode_fields(&parse_fields(&data.fields)?);
+
+            quote! {
+                let mut __frame = ::palserializer::FrameDecoder::new(data)?;
+                let __value = #name #fields;
+                __frame.finish()?;
+                ::core::result::Result::Ok(__value)
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+
+            for (index, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let index = u32::try_from(index).map_err(|_| Error::new_spanned(variant, "too many variants"))?;
+                let fields = decode_fields(&parse_fields(&variant.fields)?);
+
+                arms.push(quote! {
+                    #index => #name::#variant_name #fields,
+                });
+            }
+
+            let count = data.variants.len();
+
+            quote! {
+                let mut __frame = ::palserializer::FrameDecoder::new(data)?;
+                let __value = match __frame.read::<u32>()? {
+                    #(#arms)*
+                    __index => {
+                        return ::core::result::Result::Err(::palserializer::PalsError::InvalidValue {
+                            offset: 0,
+                            segment: 0,
+                            expected: #count,
+                            available: __index as usize,
+                        });
+                    }
+                };
+                __frame.finish()?;
+                ::core::result::Result::Ok(__value)
+            }
+        }
+        Data::Union(data) => {
+            return Err(Error::new_spanned(data.union_token, "`PalsDecode` cannot be derived for unions"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::palserializer::Decode<#data_lifetime> for #name #ty_generics #where_clause {
+            fn decode(data: &#data_lifetime [u8]) -> ::core::result::Result<Self, ::palserializer::PalsError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,540 @@
+// The `pals` command-line tool, for looking at and building frames on disk
+// without writing a Rust program around `deserialize_be`.
+//
+// Every command reads a single frame from a file, or from stdin when no file
+// or `-` is given. Frames that start with a preamble are always recognised;
+// otherwise the layout comes from `--format`, or is guessed by trying the BE,
+// LE and varint layouts in that order and taking the first one that fits the
+// input exactly. A varint frame whose segments are all shorter than 127 bytes
+// is also a valid LE frame, and is reported as one. If no layout fits, the
+// error reported is that of the layout that got furthest into the input.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use palserializer::{
+    deserialize_auto_partial, deserialize_with_partial, serialize_with, serialize_with_preamble, ByteOrder, PalsConfig,
+    PalsError, Preamble, Width,
+};
+
+const USAGE: &str = "\
+usage: pals <command> [options] [file]
+
+commands:
+    inspect [file]              print the length table and a preview of every segment
+    pack <input>...             build a frame with one segment per input file (`-` is stdin)
+    unpack [file]               write every segment to its own file
+    convert --to <format> [file]
+                                rewrite a frame in another layout
+
+options:
+    -f, --format <format>       layout of the input: auto (default), le, be or varint;
+                                for `pack`, the layout of the output (default be)
+    -t, --to <format>           layout of the output of `convert`: le, be or varint
+    -o, --output <file>         where `pack` and `convert` write the frame (default stdout)
+    -d, --dir <dir>             where `unpack` writes the segments (default .)
+    -p, --preamble              start the frame written by `pack` or `convert` with a preamble
+    -h, --help                  print this message
+
+exit codes:
+    0  success
+    2  invalid arguments
+    3  a file could not be read or written
+    4  the frame is cut short
+    5  the frame is malformed or not in the expected layout
+    6  a checksum does not match
+    7  the segments do not fit the requested layout
+";
+
+// Exit codes, one per kind of failure, so scripts can tell them apart.
+const EXIT_USAGE: u8 = 2;
+const EXIT_IO: u8 = 3;
+const EXIT_INCOMPLETE: u8 = 4;
+const EXIT_MALFORMED: u8 = 5;
+const EXIT_CHECKSUM: u8 = 6;
+const EXIT_ENCODE: u8 = 7;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args, &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pals: {}", e);
+            if e.code() == EXIT_USAGE {
+                eprint!("\n{}", USAGE);
+            }
+            ExitCode::from(e.code())
+        }
+    }
+}
+
+// Everything that can make a command fail.
+#[derive(Debug)]
+enum Error {
+    Usage(String),
+    Io(PathBuf, io::Error),
+    Pals(PalsError),
+}
+
+impl Error {
+    fn code(&self) -> u8 {
+        match self {
+            Error::Usage(_) => EXIT_USAGE,
+            Error::Io(..) => EXIT_IO,
+            Error::Pals(e) if e.is_incomplete() => EXIT_INCOMPLETE,
+            Error::Pals(PalsError::SegmentChecksumMismatch { .. } | PalsError::FrameChecksumMismatch { .. }) => {
+                EXIT_CHECKSUM
+            }
+            Error::Pals(PalsError::SegmentTooLarge { .. } | PalsError::EmptyInput { .. }) => EXIT_ENCODE,
+            Error::Pals(_) => EXIT_MALFORMED,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Pals(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<PalsError> for Error {
+    fn from(e: PalsError) -> Self {
+        Error::Pals(e)
+    }
+}
+
+// The layout named by `--format` or `--to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Auto,
+    Le,
+    Be,
+    Varint,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Format, Error> {
+        match name {
+            "auto" => Ok(Format::Auto),
+            "le" => Ok(Format::Le),
+            "be" => Ok(Format::Be),
+            "varint" => Ok(Format::Varint),
+            _ => Err(Error::Usage(format!("unknown format `{}`, expected auto, le, be or varint", name))),
+        }
+    }
+
+    fn config(self) -> Option<PalsConfig> {
+        match self {
+            Format::Auto => None,
+            Format::Le => Some(PalsConfig::LE),
+            Format::Be => Some(PalsConfig::BE),
+            Format::Varint => Some(PalsConfig::VARINT),
+        }
+    }
+}
+
+// The parsed command line.
+#[derive(Debug, Default)]
+struct Options {
+    command: String,
+    format: Option<Format>,
+    to: Option<Format>,
+    output: Option<PathBuf>,
+    dir: Option<PathBuf>,
+    preamble: bool,
+    help: bool,
+    inputs: Vec<PathBuf>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, Error> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next().ok_or_else(|| Error::Usage(format!("`{}` needs a value", name)))
+            };
+
+            match arg.as_str() {
+                "-f" | "--format" => options.format = Some(Format::parse(value(arg)?)?),
+                "-t" | "--to" => options.to = Some(Format::parse(value(arg)?)?),
+                "-o" | "--output" => options.output = Some(PathBuf::from(value(arg)?)),
+                "-d" | "--dir" => options.dir = Some(PathBuf::from(value(arg)?)),
+                "-p" | "--preamble" => options.preamble = true,
+                "-h" | "--help" => options.help = true,
+                "-" => options.inputs.push(PathBuf::from(arg)),
+                _ if arg.starts_with('-') => return Err(Error::Usage(format!("unknown option `{}`", arg))),
+                _ if options.command.is_empty() => options.command = arg.clone(),
+                _ => options.inputs.push(PathBuf::from(arg)),
+            }
+        }
+
+        Ok(options)
+    }
+
+    // The single input file of a command that reads one frame.
+    fn input(&self) -> Result<&Path, Error> {
+        match self.inputs.as_slice() {
+            [] => Ok(Path::new("-")),
+            [input] => Ok(input),
+            _ => Err(Error::Usage(format!("`{}` takes a single input file", self.command))),
+        }
+    }
+}
+
+fn run(args: &[String], stdout: &mut impl Write) -> Result<(), Error> {
+    let options = Options::parse(args)?;
+
+    if options.help {
+        return stdout.write_all(USAGE.as_bytes()).map_err(|e| Error::Io(PathBuf::from("-"), e));
+    }
+
+    match options.command.as_str() {
+        "inspect" => {
+            let data = read_input(options.input()?)?;
+            let frame = Decoded::parse(&data, options.format.unwrap_or(Format::Auto))?;
+            frame.inspect(stdout).map_err(|e| Error::Io(PathBuf::from("-"), e))
+        }
+        "pack" => {
+            let config = match options.format.unwrap_or(Format::Be) {
+                Format::Auto => return Err(Error::Usage(String::from("`pack` needs an explicit `--format`"))),
+                format => format.config().unwrap(),
+            };
+            if options.inputs.is_empty() {
+                return Err(Error::Usage(String::from("`pack` needs at least one input file")));
+            }
+
+            let inputs = options.inputs.iter().map(|i| read_input(i)).collect::<Result<Vec<_>, _>>()?;
+            let segments: Vec<&[u8]> = inputs.iter().map(|i| i.as_slice()).collect();
+            write_output(options.output.as_deref(), &encode(&config, &segments, options.preamble)?, stdout)
+        }
+        "unpack" => {
+            let data = read_input(options.input()?)?;
+            let frame = Decoded::parse(&data, options.format.unwrap_or(Format::Auto))?;
+            let dir = options.dir.as_deref().unwrap_or(Path::new("."));
+
+            for (index, segment) in frame.segments.iter().enumerate() {
+                let path = dir.join(format!("segment-{:04}", index));
+                fs::write(&path, segment).map_err(|e| Error::Io(path, e))?;
+            }
+            Ok(())
+        }
+        "convert" => {
+            let config = match options.to {
+                Some(Format::Auto) | None => {
+                    return Err(Error::Usage(String::from("`convert` needs `--to le`, `--to be` or `--to varint`")))
+                }
+                Some(format) => format.config().unwrap(),
+            };
+
+            let data = read_input(options.input()?)?;
+            let frame = Decoded::parse(&data, options.format.unwrap_or(Format::Auto))?;
+            write_output(options.output.as_deref(), &encode(&config, &frame.segments, options.preamble)?, stdout)
+        }
+        "" => Err(Error::Usage(String::from("missing command"))),
+        command => Err(Error::Usage(format!("unknown command `{}`", command))),
+    }
+}
+
+fn encode(config: &PalsConfig, segments: &[&[u8]], preamble: bool) -> Result<Vec<u8>, Error> {
+    if preamble {
+        Ok(serialize_with_preamble(config, segments)?)
+    } else {
+        Ok(serialize_with(config, segments)?)
+    }
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>, Error> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        return Ok(data);
+    }
+
+    fs::read(path).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+fn write_output(path: Option<&Path>, data: &[u8], stdout: &mut impl Write) -> Result<(), Error> {
+    match path {
+        Some(path) if path != Path::new("-") => fs::write(path, data).map_err(|e| Error::Io(path.to_path_buf(), e)),
+        _ => stdout.write_all(data).and_then(|_| stdout.flush()).map_err(|e| Error::Io(PathBuf::from("-"), e)),
+    }
+}
+
+// A frame that fills the whole input, along with the layout it was read with.
+struct Decoded<'a> {
+    data: &'a [u8],
+    config: PalsConfig,
+    preamble: bool,
+    segments: Vec<&'a [u8]>,
+}
+
+impl<'a> Decoded<'a> {
+    fn parse(data: &'a [u8], format: Format) -> Result<Self, Error> {
+        if data.starts_with(&Preamble::MAGIC) {
+            let config = Preamble::read(data)?.config;
+            let (segments, end) = deserialize_auto_partial(data)?;
+            return Ok(Decoded::exact(data, config, true, segments, end)?);
+        }
+
+        if let Some(config) = format.config() {
+            return Ok(Decoded::parse_with(data, config)?);
+        }
+
+        // Without a preamble, take the first layout that explains all of the input,
+        // or report the error of the one that got furthest.
+        let mut furthest: Option<PalsError> = None;
+        for config in [PalsConfig::BE, PalsConfig::LE, PalsConfig::VARINT] {
+            match Decoded::parse_with(data, config) {
+                Ok(frame) => return Ok(frame),
+                Err(e) if furthest.as_ref().is_some_and(|i| i.offset() >= e.offset()) => {}
+                Err(e) => furthest = Some(e),
+            }
+        }
+        Err(furthest.unwrap().into())
+    }
+
+    fn parse_with(data: &'a [u8], config: PalsConfig) -> Result<Self, PalsError> {
+        let (segments, end) = deserialize_with_partial(&config, data)?;
+        Decoded::exact(data, config, false, segments, end)
+    }
+
+    // Accept the frame that ends at `end` only if nothing follows it.
+    fn exact(
+        data: &'a [u8],
+        config: PalsConfig,
+        preamble: bool,
+        segments: Vec<&'a [u8]>,
+        end: usize,
+    ) -> Result<Self, PalsError> {
+        if end != data.len() {
+            let (segment, available) = (segments.len(), data.len());
+            return Err(PalsError::TrailingBytes { offset: end, segment, expected: end, available });
+        }
+        Ok(Decoded { data, config, preamble, segments })
+    }
+
+    // The offset and size of every length table entry, terminator included, for display.
+    fn table_entries(&self) -> Vec<(usize, usize)> {
+        let mut offset = if self.preamble { Preamble::LEN } else { 0 };
+        let mut entries = Vec::with_capacity(self.segments.len() + 1);
+
+        for _ in 0..=self.segments.len() {
+            let len = match self.config.width {
+                Width::U8 => 1,
+                Width::U16 => 2,
+                Width::U32 => 4,
+                Width::U64 => 8,
+                // The entry ends with the first byte without the continuation bit.
+                Width::Varint => self.data[offset..].iter().position(|b| b & 0x80 == 0).unwrap() + 1,
+            };
+            entries.push((offset, len));
+            offset += len;
+        }
+
+        entries
+    }
+
+    fn inspect(&self, out: &mut impl Write) -> io::Result<()> {
+        let entries = self.table_entries();
+        let payload_start = entries.last().map(|(offset, len)| offset + len).unwrap_or(0);
+        let payload_len: usize = self.segments.iter().map(|i| i.len()).sum();
+
+        writeln!(out, "format:    {}", describe(&self.config))?;
+        writeln!(out, "preamble:  {}", if self.preamble { "yes" } else { "no" })?;
+        writeln!(out, "checksum:  {:?}", self.config.checksum)?;
+        writeln!(out, "segments:  {}", self.segments.len())?;
+        writeln!(
+            out,
+            "size:      {} bytes (length table {}, payload {}, trailer {})",
+            self.data.len(),
+            payload_start - entries[0].0,
+            payload_len,
+            self.data.len() - payload_start - payload_len
+        )?;
+
+        writeln!(out, "\nlength table:")?;
+        writeln!(out, "  {:>7}  {:>8}  bytes", "entry", "offset")?;
+        for (index, (offset, len)) in entries.iter().enumerate() {
+            let name = if index == self.segments.len() { String::from("end") } else { index.to_string() };
+            writeln!(out, "  {:>7}  {:>8}  {}", name, offset, hex(&self.data[*offset..(offset + len)]))?;
+        }
+
+        writeln!(out, "\nsegments:")?;
+        writeln!(out, "  {:>7}  {:>8}  {:>8}  preview", "index", "offset", "length")?;
+        for (index, segment) in self.segments.iter().enumerate() {
+            let offset = segment.as_ptr() as usize - self.data.as_ptr() as usize;
+            writeln!(out, "  {:>7}  {:>8}  {:>8}  {}", index, offset, segment.len(), preview(segment))?;
+        }
+
+        Ok(())
+    }
+}
+
+// A short name for the layout of `config`, such as "be (u64 big-endian)".
+fn describe(config: &PalsConfig) -> String {
+    let name = match (config.width, config.order) {
+        (Width::U8, _) => "le",
+        (Width::U64, ByteOrder::Big) => "be",
+        (Width::Varint, _) => "varint",
+        _ => "custom",
+    };
+    let width = match config.width {
+        Width::U8 => "u8",
+        Width::U16 => "u16",
+        Width::U32 => "u32",
+        Width::U64 => "u64",
+        Width::Varint => return String::from("varint (LEB128)"),
+    };
+    let order = match config.order {
+        ByteOrder::Little => "little-endian",
+        ByteOrder::Big => "big-endian",
+    };
+    format!("{} ({} {})", name, width, order)
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+// The first bytes of `segment` in hex, followed by the text if it is UTF-8.
+fn preview(segment: &[u8]) -> String {
+    const MAX: usize = 16;
+
+    let mut preview = hex(&segment[..segment.len().min(MAX)]);
+    if segment.len() > MAX {
+        preview.push_str(" ..");
+    }
+
+    if let Ok(text) = std::str::from_utf8(segment) {
+        if !text.is_empty() {
+            let short: String = text.chars().take(MAX).collect();
+            preview.push_str(&format!("  {:?}{}", short, if short.len() < text.len() { ".." } else { "" }));
+        }
+    }
+
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palserializer::{serialize_be, serialize_le, serialize_varint, Checksum};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let be = serialize_be(&[b"hello", b"world"]).unwrap();
+        let le = serialize_le(&[b"hello", b"world"]).unwrap();
+        let varint = serialize_varint(&[&[1; 300]]).unwrap();
+
+        assert_eq!(Decoded::parse(&be, Format::Auto).unwrap().config, PalsConfig::BE);
+        assert_eq!(Decoded::parse(&le, Format::Auto).unwrap().config, PalsConfig::LE);
+        assert_eq!(Decoded::parse(&varint, Format::Auto).unwrap().config, PalsConfig::VARINT);
+        assert_eq!(Decoded::parse(&le, Format::Auto).unwrap().segments, [b"hello", b"world"]);
+
+        let config = PalsConfig::LE.with_checksum(Checksum::Both);
+        let preamble = serialize_with_preamble(&config, &[b"abc"]).unwrap();
+        let frame = Decoded::parse(&preamble, Format::Be).unwrap();
+        assert_eq!(frame.config, config);
+        assert_eq!(frame.segments, [b"abc"]);
+
+        let err = Decoded::parse(&[preamble.as_slice(), b"x"].concat(), Format::Auto).err().unwrap();
+        let (len, segment) = (preamble.len(), 1);
+        let expected = PalsError::TrailingBytes { offset: len, segment, expected: len, available: len + 1 };
+        assert!(matches!(&err, Error::Pals(e) if *e == expected));
+    }
+
+    #[test]
+    fn test_detect_format_reports_furthest_error() {
+        // The last length is one short, so the LE layout decodes a frame that ends a byte early,
+        // while the BE layout already fails in the length table.
+        let mut le = serialize_le(&[b"hello", b"world"]).unwrap();
+        le[1] -= 1;
+
+        let be = Decoded::parse_with(&le, PalsConfig::BE).err().unwrap();
+        assert!(matches!(be, PalsError::MissingTerminator { offset: 8, .. }));
+
+        let err = Decoded::parse(&le, Format::Auto).err().unwrap();
+        let expected = PalsError::TrailingBytes { offset: 12, segment: 2, expected: 12, available: 13 };
+        assert!(matches!(&err, Error::Pals(e) if *e == expected));
+        assert_eq!(err.code(), EXIT_MALFORMED);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let be = serialize_be(&[b"hello"]).unwrap();
+        let code = |data: &[u8], format| Decoded::parse(data, format).err().unwrap().code();
+
+        assert_eq!(code(&be[..(be.len() - 1)], Format::Be), EXIT_INCOMPLETE);
+        assert_eq!(code(&[be.as_slice(), b"x"].concat(), Format::Be), EXIT_MALFORMED);
+        assert_eq!(code(b"PALS\x09\x03\x00\x00\x00", Format::Auto), EXIT_MALFORMED);
+        assert_eq!(code(b"PALS", Format::Auto), EXIT_INCOMPLETE);
+
+        let config = PalsConfig::BE.with_checksum(Checksum::Frame);
+        let mut checked = serialize_with_preamble(&config, &[b"abc"]).unwrap();
+        checked[25] ^= 1;
+        assert_eq!(code(&checked, Format::Auto), EXIT_CHECKSUM);
+
+        assert_eq!(run(&args(&["frobnicate"]), &mut Vec::new()).unwrap_err().code(), EXIT_USAGE);
+        assert_eq!(run(&args(&["inspect", "--format", "xml"]), &mut Vec::new()).unwrap_err().code(), EXIT_USAGE);
+        assert_eq!(run(&args(&["inspect", "/nonexistent/frame"]), &mut Vec::new()).unwrap_err().code(), EXIT_IO);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let frame = serialize_varint(&[b"hi", &[0xff; 20]]).unwrap();
+        let mut out = Vec::new();
+
+        Decoded::parse(&frame, Format::Varint).unwrap().inspect(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("format:    varint (LEB128)"));
+        assert!(out.contains("segments:  2"));
+        assert!(out.contains("size:      25 bytes (length table 3, payload 22, trailer 0)"));
+        assert!(out.contains("        0         0  03\n"));
+        assert!(out.contains("      end         2  00\n"));
+        assert!(out.contains("        0         3         2  68 69  \"hi\"\n"));
+        assert!(out.contains("ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ..\n"));
+    }
+
+    #[test]
+    fn test_pack_unpack_convert() {
+        let dir = std::env::temp_dir().join(format!("pals-cli-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        fs::write(path("a"), b"first").unwrap();
+        fs::write(path("b"), b"").unwrap();
+
+        run(&args(&["pack", "-f", "le", "-o", &path("frame"), &path("a"), &path("b")]), &mut Vec::new()).unwrap();
+        assert_eq!(fs::read(path("frame")).unwrap(), serialize_le(&[b"first", b""]).unwrap());
+
+        let mut out = Vec::new();
+        run(&args(&["convert", "--to", "be", &path("frame")]), &mut out).unwrap();
+        assert_eq!(out, serialize_be(&[b"first", b""]).unwrap());
+
+        fs::write(path("frame"), &out).unwrap();
+        run(&args(&["unpack", "-d", &dir.to_string_lossy(), &path("frame")]), &mut Vec::new()).unwrap();
+        assert_eq!(fs::read(path("segment-0000")).unwrap(), b"first");
+        assert_eq!(fs::read(path("segment-0001")).unwrap(), b"");
+
+        fs::write(path("big"), [0; 300]).unwrap();
+        let err = run(&args(&["pack", "--format", "le", &path("big")]), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.code(), EXIT_ENCODE);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
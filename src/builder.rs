@@ -0,0 +1,235 @@
+use alloc::vec::Vec;
+
+use crate::checksum::{write_trailer, Crc32c};
+use crate::output::{Output, SliceOutput};
+use crate::{check_head, write_head, PalsConfig, PalsError};
+
+// Builds a frame one segment at a time, without first collecting the
+// segments into a `&[&[u8]]`. The payload is kept in a single buffer, and
+// `finish` writes the length table, payload and checksums into an output
+// sized exactly for them.
+#[derive(Debug, Clone)]
+pub struct FrameBuilder {
+    config: PalsConfig,
+    lengths: Vec<usize>,
+    payload: Vec<u8>,
+}
+
+impl FrameBuilder {
+    // Create a builder for a frame laid out as described by `config`.
+    pub fn with_config(config: PalsConfig) -> Self {
+        FrameBuilder { config, lengths: Vec::new(), payload: Vec::new() }
+    }
+
+    // Create a builder for a frame laid out like the output of `serialize_le`.
+    pub fn le() -> Self {
+        Self::with_config(PalsConfig::LE)
+    }
+
+    // Create a builder for a frame laid out like the output of `serialize_be`.
+    pub fn be() -> Self {
+        Self::with_config(PalsConfig::BE)
+    }
+
+    // Create a builder for a frame laid out like the output of `serialize_varint`.
+    pub fn varint() -> Self {
+        Self::with_config(PalsConfig::VARINT)
+    }
+
+    // Append a segment holding `segment`.
+    pub fn push(&mut self, segment: impl AsRef<[u8]>) -> &mut Self {
+        let segment = segment.as_ref();
+        self.payload.extend_from_slice(segment);
+        self.lengths.push(segment.len());
+        self
+    }
+
+    // Append a segment holding the UTF-8 bytes of `segment`.
+    pub fn push_str(&mut self, segment: &str) -> &mut Self {
+        self.push(segment)
+    }
+
+    // Append a segment holding the frame built by `nested`.
+    pub fn push_frame(&mut self, nested: &FrameBuilder) -> Result<&mut Self, PalsError> {
+        let start = self.payload.len();
+        nested.finish_into(&mut self.payload)?;
+        self.lengths.push(self.payload.len() - start);
+        Ok(self)
+    }
+
+    // Reserve room for at least `additional` more payload bytes.
+    pub fn reserve(&mut self, additional: usize) -> &mut Self {
+        self.payload.reserve(additional);
+        self
+    }
+
+    // The number of segments pushed so far.
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    // The size of the finished frame in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.config.header_len(self.lengths.iter().copied())
+            + self.payload.len()
+            + self.config.checksum.trailer_len(self.lengths.len())
+    }
+
+    // Build the frame into a new vector.
+    pub fn finish(self) -> Result<Vec<u8>, PalsError> {
+        let mut output = Vec::with_capacity(self.encoded_len());
+        self.finish_into(&mut output)?;
+        Ok(output)
+    }
+
+    // Append the frame to `output`. On failure `output` is left as it was.
+    pub fn finish_into(&self, output: &mut Vec<u8>) -> Result<(), PalsError> {
+        check_head(&self.config, self.lengths.iter().copied())?;
+
+        output.reserve(self.encoded_len());
+        self.write_to(output)
+    }
+
+    // Write the frame to the start of `output` instead of allocating, and
+    // return its length, like `serialize_with_into`. Fails with
+    // `PalsError::BufferTooSmall` if `output` cannot hold the whole frame.
+    pub fn finish_into_slice(&self, output: &mut [u8]) -> Result<usize, PalsError> {
+        check_head(&self.config, self.lengths.iter().copied())?;
+
+        let len = self.encoded_len();
+        if output.len() < len {
+            return Err(PalsError::BufferTooSmall { offset: 0, segment: 0, expected: len, available: output.len() });
+        }
+
+        self.write_to(&mut SliceOutput::new(output))?;
+        Ok(len)
+    }
+
+    // Append the frame to `output`, which must have room for it.
+    fn write_to(&self, output: &mut impl Output) -> Result<(), PalsError> {
+        let start = output.len();
+
+        write_head(&self.config, self.lengths.iter().copied(), false, output)?;
+        output.put(&self.payload);
+
+        let frame = self.config.checksum.frame().then(|| {
+            let mut hasher = Crc32c::new();
+            hasher.update(&output.written()[start..]);
+            hasher
+        });
+        write_trailer(self.config.checksum, self.segments(), frame, output);
+
+        Ok(())
+    }
+
+    // The segments pushed so far, as slices of the payload.
+    fn segments(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.lengths.iter().scan(0, |start, len| {
+            let segment = &self.payload[*start..(*start + len)];
+            *start += len;
+            Some(segment)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_be, serialize_be, serialize_with, ByteOrder, Checksum, Width};
+
+    #[test]
+    fn test_build_matches_serialize_every_config() {
+        let data: [&[u8]; 4] = [b"abc", b"", &[9; 200], b"z"];
+
+        for width in [Width::U8, Width::U16, Width::U32, Width::U64, Width::Varint] {
+            for checksum in [Checksum::None, Checksum::Both] {
+                let config = PalsConfig::new(width, ByteOrder::Big).with_checksum(checksum);
+
+                let mut builder = FrameBuilder::with_config(config);
+                builder.push(data[0]).push(Vec::new()).push(data[2]).push_str("z");
+                assert_eq!(builder.len(), 4);
+
+                let encoded_len = builder.encoded_len();
+                let frame = builder.finish().unwrap();
+
+                assert_eq!(frame, serialize_with(&config, &data).unwrap());
+                assert_eq!(frame.len(), encoded_len);
+                assert_eq!(frame.capacity(), encoded_len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_frame() {
+        let mut inner = FrameBuilder::be();
+        inner.push("a").push("b");
+
+        let mut outer = FrameBuilder::be();
+        outer.push_str("head").push_frame(&inner).unwrap();
+        let frame = outer.finish().unwrap();
+
+        let segments = deserialize_be(&frame).unwrap();
+        assert_eq!(segments[1], serialize_be(&[b"a", b"b"]).unwrap());
+    }
+
+    #[test]
+    fn test_finish_into_appends() {
+        let mut builder = FrameBuilder::le();
+        builder.reserve(3).push([1, 2, 3]);
+
+        let mut output = b"prefix".to_vec();
+        builder.finish_into(&mut output).unwrap();
+
+        assert_eq!(output, b"prefix\x04\x00\x01\x02\x03");
+    }
+
+    #[test]
+    fn test_finish_into_slice() {
+        let config = PalsConfig::LE.with_checksum(Checksum::Both);
+        let mut builder = FrameBuilder::with_config(config);
+        builder.push("ab").push("").push("cde");
+
+        let mut buffer = [0xaa; 64];
+        let len = builder.finish_into_slice(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], serialize_with(&config, &[b"ab", b"", b"cde"]).unwrap());
+        assert_eq!(buffer[len], 0xaa);
+
+        let mut small = [0; 8];
+        assert!(matches!(
+            builder.finish_into_slice(&mut small),
+            Err(PalsError::BufferTooSmall { expected, available: 8, .. }) if expected == len
+        ));
+        assert_eq!(small, [0; 8]);
+
+        // An unencodable frame is reported as such, not as a buffer that is too small.
+        let mut builder = FrameBuilder::le();
+        builder.push([0; 300]);
+        assert!(matches!(builder.finish_into_slice(&mut small), Err(PalsError::SegmentTooLarge { .. })));
+    }
+
+    #[test]
+    fn test_finish_too_large_leaves_output_alone() {
+        let mut builder = FrameBuilder::le();
+        builder.push([0; 300]);
+
+        let mut output = b"prefix".to_vec();
+        let result = builder.finish_into(&mut output);
+
+        assert!(matches!(result, Err(PalsError::SegmentTooLarge { segment: 0, expected: 300, .. })));
+        assert_eq!(output, b"prefix");
+
+        let mut outer = FrameBuilder::be();
+        assert!(outer.push_frame(&builder).is_err());
+        assert!(outer.is_empty());
+    }
+
+    #[test]
+    fn test_build_empty_frame() {
+        assert_eq!(FrameBuilder::varint().finish().unwrap(), [0]);
+        assert!(FrameBuilder::with_config(PalsConfig::BE.with_reject_empty(true)).finish().is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::output::Output;
+use crate::PalsError;
+
+// Which checksums a frame carries.
+// Checksums are stored in a trailer right after the payload: first one
+// big-endian CRC32C per segment, computed over that segment's bytes, then one
+// over everything in the frame before it (preamble, length table, payload and
+// segment checksums).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Checksum {
+    #[default]
+    None,
+    Segments,
+    Frame,
+    Both,
+}
+
+impl Checksum {
+    // Whether every segment carries its own checksum.
+    pub fn segments(self) -> bool {
+        matches!(self, Checksum::Segments | Checksum::Both)
+    }
+
+    // Whether the frame as a whole carries a checksum.
+    pub fn frame(self) -> bool {
+        matches!(self, Checksum::Frame | Checksum::Both)
+    }
+
+    // Build a checksum mode from its two halves.
+    pub fn from_parts(segments: bool, frame: bool) -> Self {
+        match (segments, frame) {
+            (false, false) => Checksum::None,
+            (true, false) => Checksum::Segments,
+            (false, true) => Checksum::Frame,
+            (true, true) => Checksum::Both,
+        }
+    }
+
+    // The size of the checksum trailer of a frame with `segments` segments.
+    pub(crate) fn trailer_len(self, segments: usize) -> usize {
+        let mut len = 0;
+        if self.segments() {
+            len += 4 * segments;
+        }
+        if self.frame() {
+            len += 4;
+        }
+        len
+    }
+}
+
+// The CRC32C (Castagnoli) lookup table, built at compile time.
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// An incremental CRC32C hasher, for data that is not available in one piece.
+#[derive(Debug, Clone)]
+pub(crate) struct Crc32c(u32);
+
+impl Crc32c {
+    pub(crate) fn new() -> Self {
+        Crc32c(!0)
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.0 = TABLE[((self.0 ^ *byte as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+// The CRC32C of `data`.
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    let mut hasher = Crc32c::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+// Append the checksum trailer of a frame to `output`: the CRC32C of every
+// segment yielded by `segments` if the frame has segment checksums, then the
+// frame checksum. `frame` must be `Some` exactly when the frame has a frame
+// checksum, holding a hasher that has already seen everything in the frame
+// before the trailer. Every encoder writes its trailer through here, so they
+// all stay in line with `verify_trailer`.
+pub(crate) fn write_trailer<'a>(
+    checksum: Checksum,
+    segments: impl Iterator<Item = &'a [u8]>,
+    frame: Option<Crc32c>,
+    output: &mut impl Output,
+) {
+    let start = output.len();
+
+    if checksum.segments() {
+        for i in segments {
+            output.put(&crc32c(i).to_be_bytes());
+        }
+    }
+
+    if let Some(mut hasher) = frame {
+        hasher.update(&output.written()[start..]);
+        output.put(&hasher.finish().to_be_bytes());
+    }
+}
+
+// Check the checksum trailer that starts `offset` bytes into the frame.
+// `segment_crcs` yields the CRC32C of every segment and is only consumed if
+// the frame has segment checksums. `frame_crc` is only called if the frame has
+// a frame checksum, and must return the CRC32C of everything in the frame
+// before that checksum.
+pub(crate) fn verify_trailer(
+    checksum: Checksum,
+    segment_crcs: impl ExactSizeIterator<Item = u32>,
+    trailer: &[u8],
+    offset: usize,
+    frame_crc: impl FnOnce() -> u32,
+) -> Result<(), PalsError> {
+    let segments = segment_crcs.len();
+    let mut i = 0;
+
+    if checksum.segments() {
+        for (segment, actual) in segment_crcs.enumerate() {
+            let stored = u32::from_be_bytes(trailer[i..(i + 4)].try_into().unwrap());
+
+            if stored != actual {
+                return Err(PalsError::SegmentChecksumMismatch {
+                    offset: offset + i,
+                    segment,
+                    expected: stored as usize,
+                    available: actual as usize,
+                });
+            }
+
+            i += 4;
+        }
+    }
+
+    if checksum.frame() {
+        let stored = u32::from_be_bytes(trailer[i..(i + 4)].try_into().unwrap());
+        let actual = frame_crc();
+
+        if stored != actual {
+            return Err(PalsError::FrameChecksumMismatch {
+                offset: offset + i,
+                segment: segments,
+                expected: stored as usize,
+                available: actual as usize,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_auto, deserialize_with, serialize_with, serialize_with_preamble, PalsConfig};
+
+    #[test]
+    fn test_crc32c_known_values() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+
+        let mut hasher = Crc32c::new();
+        hasher.update(b"1234");
+        hasher.update(b"56789");
+        assert_eq!(hasher.finish(), 0xe306_9283);
+    }
+
+    #[test]
+    fn test_serialize_deserialize_with_checksums() {
+        let data: [&[u8]; 3] = [b"abc", b"", b"defg"];
+
+        for checksum in [Checksum::None, Checksum::Segments, Checksum::Frame, Checksum::Both] {
+            let config = PalsConfig::LE.with_checksum(checksum);
+
+            let serialized = serialize_with(&config, &data).unwrap();
+
+            assert_eq!(serialized.len(), 4 + 7 + checksum.trailer_len(3));
+            assert_eq!(deserialize_with(&config, &serialized).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_corrupted_segment() {
+        let config = PalsConfig::BE.with_checksum(Checksum::Both);
+
+        let mut serialized = serialize_with(&config, &[b"abc", b"defg"]).unwrap();
+        serialized[24 + 4] ^= 0x10; // Flip a bit in the second segment.
+
+        assert!(matches!(
+            deserialize_with(&config, &serialized),
+            Err(PalsError::SegmentChecksumMismatch { offset: 35, segment: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_corrupted_frame() {
+        let config = PalsConfig::VARINT.with_checksum(Checksum::Frame);
+
+        let mut serialized = serialize_with_preamble(&config, &[b"abc", b"defg"]).unwrap();
+        serialized[11] ^= 0x01; // Flip a bit in the first segment.
+
+        assert!(matches!(deserialize_auto(&serialized), Err(PalsError::FrameChecksumMismatch { segment: 2, .. })));
+    }
+
+    #[test]
+    fn test_deserialize_truncated_trailer() {
+        let config = PalsConfig::LE.with_checksum(Checksum::Segments);
+
+        let serialized = serialize_with(&config, &[b"abc"]).unwrap();
+
+        assert!(matches!(
+            deserialize_with(&config, &serialized[..serialized.len() - 1]),
+            Err(PalsError::TruncatedPayload { offset: 5, segment: 1, expected: 4, available: 3 })
+        ));
+    }
+}
@@ -0,0 +1,254 @@
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{decode_frame, write_frame, PalsConfig, PalsError};
+
+// A `tokio_util::codec` encoder/decoder pair for PALS frames.
+// Wrapped in `Framed`, `FramedRead` or `FramedWrite` it turns any
+// `AsyncRead`/`AsyncWrite` into a `Stream` of frames and a `Sink` accepting them.
+//
+// Decoded frames are returned as a list of `Bytes` that all share the buffer
+// the frame was read into, so no segment is copied.
+//
+// The lengths in an incoming frame come from the peer, so frames larger than
+// `max_frame_len` are rejected with `PalsError::PayloadOverLimit` before any
+// of their payload is buffered.
+#[derive(Debug, Clone)]
+pub struct PalsCodec {
+    config: PalsConfig,
+    max_frame_len: usize,
+    // The total size of the frame currently being received, once its length
+    // table is complete.
+    pending: Option<usize>,
+}
+
+// How much room to make in the read buffer at a time while waiting for the
+// rest of a frame. The declared frame size is never reserved up front.
+const RESERVE_CHUNK: usize = 64 * 1024;
+
+impl PalsCodec {
+    // The largest frame accepted by default, including its length table and checksums.
+    pub const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+    // Create a codec for frames laid out as described by `config`.
+    pub fn with_config(config: PalsConfig) -> Self {
+        PalsCodec { config, max_frame_len: PalsCodec::DEFAULT_MAX_FRAME_LEN, pending: None }
+    }
+
+    // Create a codec for frames laid out like `serialize_le`.
+    pub fn le() -> Self {
+        Self::with_config(PalsConfig::LE)
+    }
+
+    // Create a codec for frames laid out like `serialize_be`.
+    pub fn be() -> Self {
+        Self::with_config(PalsConfig::BE)
+    }
+
+    // Create a codec for frames laid out like `serialize_varint`.
+    pub fn varint() -> Self {
+        Self::with_config(PalsConfig::VARINT)
+    }
+
+    // Set the largest frame the decoder accepts, in bytes.
+    pub fn max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    fn frame_too_large(&self, segment: usize, len: usize) -> PalsError {
+        PalsError::PayloadOverLimit { offset: 0, segment, expected: self.max_frame_len, available: len }
+    }
+}
+
+impl Decoder for PalsCodec {
+    type Item = Vec<Bytes>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Vec<Bytes>>> {
+        if self.pending.is_none() {
+            let (lengths, start) = match self.config.read_header(src) {
+                Ok(header) => header,
+                Err(PalsError::MissingTerminator { segment, .. }) => {
+                    // Wait for the rest of the length table, unless it alone is already too large.
+                    if src.len() > self.max_frame_len {
+                        return Err(self.frame_too_large(segment, src.len()).into());
+                    }
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            // Work out the full frame size once, so partial payloads are cheap to check.
+            let mut end = start + self.config.checksum.trailer_len(lengths.len());
+            for (segment, len) in lengths.iter().enumerate() {
+                end = end.checked_add(*len).ok_or(PalsError::LengthOverflow {
+                    offset: end,
+                    segment,
+                    expected: usize::MAX,
+                    available: src.len(),
+                })?;
+            }
+
+            if end > self.max_frame_len {
+                return Err(self.frame_too_large(lengths.len(), end).into());
+            }
+
+            self.pending = Some(end);
+        }
+
+        let end = self.pending.unwrap_or(0);
+
+        if src.len() < end {
+            // Make room for more of the payload, but only a chunk at a time.
+            src.reserve((end - src.len()).min(RESERVE_CHUNK));
+            return Ok(None);
+        }
+
+        self.pending = None;
+        let frame = src.split_to(end).freeze();
+
+        // The frame is complete, so let the slice decoder split it and check its checksums.
+        let (segments, _) = decode_frame(&self.config, &frame, 0)?;
+
+        Ok(Some(segments.into_iter().map(|i| frame.slice_ref(i)).collect()))
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<&[T]> for PalsCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, data: &[T], dst: &mut BytesMut) -> io::Result<()> {
+        dst.reserve(
+            self.config.header_len(data.iter().map(|i| i.as_ref().len()))
+                + data.iter().map(|i| i.as_ref().len()).sum::<usize>()
+                + self.config.checksum.trailer_len(data.len()),
+        );
+
+        write_frame(&self.config, data, false, dst)?;
+
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<Vec<T>> for PalsCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, data: Vec<T>, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(data.as_slice(), dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_be, serialize_with, Checksum};
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    #[test]
+    fn test_decode_every_split() {
+        let frame = serialize_be(&[b"abc", b"", b"defgh"]).unwrap();
+
+        for split in 0..frame.len() {
+            let mut codec = PalsCodec::be();
+            let mut buf = BytesMut::from(&frame[..split]);
+
+            assert!(codec.decode(&mut buf).unwrap().is_none());
+
+            buf.extend_from_slice(&frame[split..]);
+            let decoded = codec.decode(&mut buf).unwrap().unwrap();
+
+            assert_eq!(decoded, vec![&b"abc"[..], b"", b"defgh"]);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_framed_over_duplex() {
+        let (client, server) = tokio::io::duplex(7);
+
+        let writer = tokio::spawn(async move {
+            let mut sink = FramedWrite::new(client, PalsCodec::le());
+            sink.send(vec![b"hello".to_vec(), b"world".to_vec()]).await.unwrap();
+            sink.send(vec![b"!".to_vec()]).await.unwrap();
+            sink.into_inner().shutdown().await.unwrap();
+        });
+
+        let frames = FramedRead::new(server, PalsCodec::le()).map(|i| i.unwrap()).collect::<Vec<_>>().await;
+        writer.await.unwrap();
+
+        assert_eq!(frames, vec![vec![Bytes::from("hello"), Bytes::from("world")], vec![Bytes::from("!")]]);
+    }
+
+    #[test]
+    fn test_encode_decode_with_checksums() {
+        let config = PalsConfig::LE.with_checksum(Checksum::Both);
+        let mut codec = PalsCodec::with_config(config);
+        let mut buf = BytesMut::new();
+
+        codec.encode(&[&b"abc"[..], b"de"][..], &mut buf).unwrap();
+        assert_eq!(buf, serialize_with(&config, &[b"abc", b"de"]).unwrap());
+
+        buf[3] ^= 0x01;
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<PalsError>(),
+            Some(PalsError::SegmentChecksumMismatch { segment: 0, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_framed_truncated_stream() {
+        let (mut client, server) = tokio::io::duplex(64);
+
+        let frame = serialize_be(&[b"abcdef"]).unwrap();
+        client.write_all(&frame[..frame.len() - 2]).await.unwrap();
+        drop(client);
+
+        let mut stream = FramedRead::new(server, PalsCodec::be());
+
+        assert!(stream.next().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn test_decode_hostile_header_does_not_allocate() {
+        // A complete length table declaring a segment of 2^62 bytes.
+        let mut header = ((1u64 << 62) + 1).to_be_bytes().to_vec();
+        header.extend_from_slice(&[0; 8]);
+
+        let mut buf = BytesMut::from(&header[..]);
+        let err = PalsCodec::be().decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<PalsError>(),
+            Some(PalsError::PayloadOverLimit { expected: PalsCodec::DEFAULT_MAX_FRAME_LEN, .. })
+        ));
+
+        let mut codec = PalsCodec::be().max_frame_len(usize::MAX);
+        let mut buf = BytesMut::from(&header[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert!(buf.capacity() <= header.len() + RESERVE_CHUNK);
+
+        // A length table that never ends is cut off too.
+        let mut codec = PalsCodec::le().max_frame_len(16);
+        let mut buf = BytesMut::from(&[1u8; 17][..]);
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_encode_decode_empty_frames() {
+        let mut codec = PalsCodec::varint();
+        let mut buf = BytesMut::new();
+
+        codec.encode(&[] as &[&[u8]], &mut buf).unwrap();
+        codec.encode(&[b"x"][..], &mut buf).unwrap();
+        assert_eq!(&buf[..], &[0, 2, 0, b'x']);
+
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), Vec::<Bytes>::new());
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), vec![&b"x"[..]]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+}
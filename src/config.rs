@@ -0,0 +1,382 @@
+use alloc::vec::Vec;
+
+use crate::output::Output;
+use crate::varint::{read_header_varint, varint_len, write_header_varint};
+use crate::{Checksum, PalsError};
+
+// The number of bytes used for every entry of the length table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+    // An unsigned LEB128 varint of one to nine bytes. The byte order is ignored.
+    Varint,
+}
+
+// The byte order of the entries in the length table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+// Bounds enforced while the length table of an untrusted frame is parsed, so
+// a hostile peer cannot make a decoder allocate or wait for more than it is
+// willing to accept. Every limit is checked before the entry that breaks it
+// is stored, and before any payload is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeLimits {
+    // The most segments a single frame may have.
+    pub max_segments: usize,
+    // The longest a single segment may be.
+    pub max_segment_len: usize,
+    // The most payload bytes all segments of a frame may add up to.
+    pub max_total_len: usize,
+}
+
+impl DecodeLimits {
+    // Accept anything the format itself can express.
+    pub const UNLIMITED: DecodeLimits =
+        DecodeLimits { max_segments: usize::MAX, max_segment_len: usize::MAX, max_total_len: usize::MAX };
+
+    // Check the length table entry at `offset` describing a segment of `len`
+    // bytes, given the lengths read before it and their sum in `total`.
+    pub(crate) fn check(&self, offset: usize, lengths: &[usize], len: usize, total: &mut usize) -> Result<(), PalsError> {
+        let segment = lengths.len();
+
+        if segment >= self.max_segments {
+            return Err(PalsError::TooManySegments {
+                offset,
+                segment,
+                expected: self.max_segments,
+                available: segment + 1,
+            });
+        }
+
+        if len > self.max_segment_len {
+            return Err(PalsError::SegmentOverLimit { offset, segment, expected: self.max_segment_len, available: len });
+        }
+
+        *total = total.checked_add(len).ok_or(PalsError::LengthOverflow {
+            offset,
+            segment,
+            expected: usize::MAX,
+            available: *total,
+        })?;
+
+        if *total > self.max_total_len {
+            return Err(PalsError::PayloadOverLimit { offset, segment, expected: self.max_total_len, available: *total });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits::UNLIMITED
+    }
+}
+
+// Describes how the length table of a frame is laid out, which checksums
+// follow the payload and what limits apply when decoding it.
+// Every serializer, deserializer, reader and writer in this crate is driven
+// by one of these, so frames produced with one config can be read back by
+// anything using the same config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PalsConfig {
+    pub width: Width,
+    pub order: ByteOrder,
+    pub checksum: Checksum,
+    // Only used when decoding; limits are not part of the wire format.
+    pub limits: DecodeLimits,
+    // Refuse to serialize frames without segments, as earlier versions did.
+    // A frame of just the terminator is always accepted when decoding.
+    pub reject_empty: bool,
+}
+
+impl PalsConfig {
+    // The layout written by `serialize_le`: one byte per length.
+    pub const LE: PalsConfig = PalsConfig::new(Width::U8, ByteOrder::Little);
+
+    // The layout written by `serialize_be`: a big-endian u64 per length.
+    pub const BE: PalsConfig = PalsConfig::new(Width::U64, ByteOrder::Big);
+
+    // The layout written by `serialize_varint`: a LEB128 varint per length.
+    pub const VARINT: PalsConfig = PalsConfig::new(Width::Varint, ByteOrder::Little);
+
+    pub const fn new(width: Width, order: ByteOrder) -> Self {
+        PalsConfig { width, order, checksum: Checksum::None, limits: DecodeLimits::UNLIMITED, reject_empty: false }
+    }
+
+    // Return a copy of this config that adds the given checksums to every frame.
+    pub const fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    // Return a copy of this config that rejects frames exceeding `limits`.
+    pub const fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Return a copy of this config that fails with `PalsError::EmptyInput`
+    // when asked to serialize a frame without segments.
+    pub const fn with_reject_empty(mut self, reject_empty: bool) -> Self {
+        self.reject_empty = reject_empty;
+        self
+    }
+
+    // The number of bytes to read at a time while looking for the terminator.
+    // A canonical varint never contains a zero byte, so the varint table can be
+    // scanned byte by byte just like the u8 one.
+    pub(crate) fn width(self) -> usize {
+        match self.width {
+            Width::U8 | Width::Varint => 1,
+            Width::U16 => 2,
+            Width::U32 => 4,
+            Width::U64 => 8,
+        }
+    }
+
+    // The longest segment a single length table entry can describe.
+    pub(crate) fn max_segment_len(self) -> u64 {
+        match self.width {
+            Width::U8 => u8::MAX as u64 - 1,
+            Width::U16 => u16::MAX as u64 - 1,
+            Width::U32 => u32::MAX as u64 - 1,
+            Width::U64 => u64::MAX - 1,
+            Width::Varint => crate::varint::MAX_SEGMENT_LEN,
+        }
+    }
+
+    // Append the length table for `data`, including the terminator, to `output`.
+    // A frame without segments is just the terminator.
+    pub(crate) fn write_header<T: AsRef<[u8]>>(self, data: &[T], output: &mut impl Output) -> Result<(), PalsError> {
+        self.write_lengths(data.iter().map(|i| i.as_ref().len()), output)
+    }
+
+    // Same as `write_header`, but for segments of which only the lengths are known.
+    pub(crate) fn write_lengths(
+        self,
+        lengths: impl ExactSizeIterator<Item = usize>,
+        output: &mut impl Output,
+    ) -> Result<(), PalsError> {
+        if self.reject_empty && lengths.len() == 0 {
+            return Err(PalsError::EmptyInput { offset: 0, segment: 0, expected: 1, available: 0 });
+        }
+
+        if self.width == Width::Varint {
+            return write_header_varint(lengths, output);
+        }
+
+        let width = self.width();
+        let start = output.len();
+
+        for (index, len) in lengths.enumerate() {
+            if len as u64 > self.max_segment_len() {
+                output.truncate(start);
+                return Err(PalsError::SegmentTooLarge {
+                    offset: index * width,
+                    segment: index,
+                    expected: len,
+                    available: self.max_segment_len() as usize,
+                });
+            }
+
+            // Take the low `width` bytes of the shifted length in the requested order.
+            let entry = 1 + len as u64;
+            match self.order {
+                ByteOrder::Little => output.put(&entry.to_le_bytes()[..width]),
+                ByteOrder::Big => output.put(&entry.to_be_bytes()[8 - width..]),
+            }
+        }
+
+        output.put(&[0; 8][..width]); // Add the zero terminator.
+
+        Ok(())
+    }
+
+    // The size of the length table `write_lengths` writes for `lengths`.
+    pub(crate) fn header_len(self, lengths: impl ExactSizeIterator<Item = usize>) -> usize {
+        if self.width == Width::Varint {
+            return lengths.map(|i| varint_len(i as u64 + 1)).sum::<usize>() + 1;
+        }
+
+        self.width() * (lengths.len() + 1)
+    }
+
+    // Read the length table at the start of `data`.
+    // Returns the segment lengths and the offset at which the payload starts.
+    pub(crate) fn read_header(self, data: &[u8]) -> Result<(Vec<usize>, usize), PalsError> {
+        if self.width == Width::Varint {
+            return read_header_varint(data, &self.limits);
+        }
+
+        let width = self.width();
+        let mut lengths = Vec::new(); // Initialize an empty vector to hold the lengths of the input slices.
+        let mut total = 0;
+
+        let mut i = 0; // Initialize a counter variable to keep track of the current position in the input data.
+
+        // Loop through the input data until we reach the end or encounter a zero-length slice.
+        while i + width <= data.len() && data[i..(i + width)].iter().any(|b| *b != 0) {
+            // Read the length of the next slice from the input data.
+            let mut entry = [0; 8];
+            let len = match self.order {
+                ByteOrder::Little => {
+                    entry[..width].copy_from_slice(&data[i..(i + width)]);
+                    u64::from_le_bytes(entry)
+                }
+                ByteOrder::Big => {
+                    entry[8 - width..].copy_from_slice(&data[i..(i + width)]);
+                    u64::from_be_bytes(entry)
+                }
+            } - 1;
+
+            // Check if the length is too large to be held in memory.
+            let len = usize::try_from(len).map_err(|_| PalsError::LengthOverflow {
+                offset: i,
+                segment: lengths.len(),
+                expected: usize::MAX,
+                available: data.len(),
+            })?;
+
+            // Check the length against the limits before storing it.
+            self.limits.check(i, &lengths, len, &mut total)?;
+
+            // Add the length to the lengths vector.
+            lengths.push(len);
+
+            // Move the counter variable to the start of the next slice.
+            i += width;
+        }
+
+        // Check that the zero-length slice is actually there.
+        if i + width > data.len() {
+            return Err(PalsError::MissingTerminator {
+                offset: i,
+                segment: lengths.len(),
+                expected: width,
+                available: data.len() - i,
+            });
+        }
+
+        Ok((lengths, i + width)) // Skip past the zero-length slice.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_with, serialize_with};
+
+    #[test]
+    fn test_serialize_deserialize_every_config() {
+        let data: [&[u8]; 4] = [b"abc", b"", &[9; 300], b"z"];
+
+        for width in [Width::U16, Width::U32, Width::U64, Width::Varint] {
+            for order in [ByteOrder::Little, ByteOrder::Big] {
+                let config = PalsConfig::new(width, order);
+
+                let serialized = serialize_with(&config, &data).unwrap();
+
+                assert_eq!(deserialize_with(&config, &serialized).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize_u32_little_endian() {
+        let config = PalsConfig::new(Width::U32, ByteOrder::Little);
+
+        let serialized = serialize_with(&config, &[b"ab", b"cde"]).unwrap();
+
+        assert_eq!(serialized, [3, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, b'a', b'b', b'c', b'd', b'e']);
+    }
+
+    #[test]
+    fn test_serialize_u16_too_large() {
+        let config = PalsConfig::new(Width::U16, ByteOrder::Big);
+
+        let result = serialize_with(&config, &[&[1; 10], &[0; 70_000]]);
+
+        assert!(matches!(
+            result,
+            Err(PalsError::SegmentTooLarge { offset: 2, segment: 1, expected: 70_000, available: 65_534 })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_u16_incomplete_input() {
+        let config = PalsConfig::new(Width::U16, ByteOrder::Little);
+
+        let serialized = serialize_with(&config, &[b"abc"]).unwrap();
+
+        assert!(matches!(
+            deserialize_with(&config, &serialized[..3]),
+            Err(PalsError::MissingTerminator { offset: 2, segment: 1, expected: 2, available: 1 })
+        ));
+        assert!(matches!(
+            deserialize_with(&config, &serialized[..6]),
+            Err(PalsError::TruncatedPayload { offset: 4, segment: 0, expected: 3, available: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let data: [&[u8]; 3] = [b"abc", b"de", b"f"];
+
+        for width in [Width::U8, Width::U64, Width::Varint] {
+            let config = PalsConfig::new(width, ByteOrder::Big);
+            let serialized = serialize_with(&config, &data).unwrap();
+
+            let limits = DecodeLimits { max_segments: 2, ..DecodeLimits::UNLIMITED };
+            assert!(matches!(
+                deserialize_with(&config.with_limits(limits), &serialized),
+                Err(PalsError::TooManySegments { segment: 2, expected: 2, available: 3, .. })
+            ));
+
+            let limits = DecodeLimits { max_segment_len: 2, ..DecodeLimits::UNLIMITED };
+            assert!(matches!(
+                deserialize_with(&config.with_limits(limits), &serialized),
+                Err(PalsError::SegmentOverLimit { offset: 0, segment: 0, expected: 2, available: 3 })
+            ));
+
+            let limits = DecodeLimits { max_total_len: 5, ..DecodeLimits::UNLIMITED };
+            assert!(matches!(
+                deserialize_with(&config.with_limits(limits), &serialized),
+                Err(PalsError::PayloadOverLimit { segment: 2, expected: 5, available: 6, .. })
+            ));
+
+            let limits = DecodeLimits { max_segments: 3, max_segment_len: 3, max_total_len: 6 };
+            assert_eq!(deserialize_with(&config.with_limits(limits), &serialized).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decode_limits_without_terminator() {
+        // A table of a million entries is rejected as soon as it passes the limit.
+        let header = vec![1; 1_000_000];
+        let config = PalsConfig::LE.with_limits(DecodeLimits { max_segments: 100, ..DecodeLimits::UNLIMITED });
+
+        assert!(matches!(
+            deserialize_with(&config, &header),
+            Err(PalsError::TooManySegments { offset: 100, segment: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn test_total_length_overflow() {
+        let mut header = Vec::new();
+        for _ in 0..3 {
+            header.extend_from_slice(&(u64::MAX / 2).to_be_bytes());
+        }
+        header.extend_from_slice(&[0; 8]);
+
+        assert!(matches!(deserialize_with(&PalsConfig::BE, &header), Err(PalsError::LengthOverflow { segment: 2, .. })));
+    }
+}
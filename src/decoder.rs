@@ -0,0 +1,526 @@
+use alloc::vec::Vec;
+
+use crate::checksum::{verify_trailer, Crc32c};
+use crate::varint::MAX_VARINT_LEN;
+use crate::{PalsConfig, PalsError, Width};
+
+// A decoder that does no I/O of its own, for event loops that receive data in
+// chunks of whatever size the network hands out. Chunks are passed to `feed`,
+// which turns them into a stream of events as far as they go. Segments are
+// reported in pieces as their bytes arrive, so a large segment never has to be
+// buffered whole; only the length table and the checksum trailer are.
+//
+// Frames can follow each other back to back. The length table is validated by
+// the same code as `deserialize_with`, and any error is the one
+// `deserialize_with` would report for the frame, with offsets relative to its
+// start. After an error the decoder stays failed.
+#[derive(Debug, Clone)]
+pub struct PalsDecoder {
+    config: PalsConfig,
+    state: State,
+    // The bytes of the current frame consumed so far.
+    offset: usize,
+    // The length table read so far, and how far into its last entry we are.
+    header: Vec<u8>,
+    entry_len: usize,
+    entries: usize,
+    lengths: Vec<usize>,
+    // The checksum trailer read so far.
+    trailer: Vec<u8>,
+    segment_crcs: Vec<u32>,
+    segment_hasher: Crc32c,
+    frame_hasher: Crc32c,
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    Header,
+    Segment { index: usize, remaining: usize },
+    Trailer,
+    Failed(PalsError),
+}
+
+// What `PalsDecoder::feed` found in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    // The length table of a new frame was read.
+    Header { lengths: Vec<usize> },
+    // The next bytes of segment `index`, borrowed from the fed chunk.
+    SegmentChunk { index: usize, bytes: &'a [u8] },
+    // All bytes of segment `index` have been reported. Its checksum, if any,
+    // is only checked once the trailer has been read.
+    SegmentEnd { index: usize },
+    // The frame is complete and its checksums match.
+    FrameEnd,
+}
+
+impl PalsDecoder {
+    // Create a decoder for frames laid out as described by `config`.
+    pub fn with_config(config: PalsConfig) -> Self {
+        PalsDecoder {
+            config,
+            state: State::Header,
+            offset: 0,
+            header: Vec::new(),
+            entry_len: 0,
+            entries: 0,
+            lengths: Vec::new(),
+            trailer: Vec::new(),
+            segment_crcs: Vec::new(),
+            segment_hasher: Crc32c::new(),
+            frame_hasher: Crc32c::new(),
+        }
+    }
+
+    // Create a decoder for frames written by `serialize_le`.
+    pub fn le() -> Self {
+        Self::with_config(PalsConfig::LE)
+    }
+
+    // Create a decoder for frames written by `serialize_be`.
+    pub fn be() -> Self {
+        Self::with_config(PalsConfig::BE)
+    }
+
+    // Create a decoder for frames written by `serialize_varint`.
+    pub fn varint() -> Self {
+        Self::with_config(PalsConfig::VARINT)
+    }
+
+    // Decode the next chunk of input. The returned iterator yields the events
+    // found in `data` and consumes it as it goes, so it has to be drained
+    // before the next chunk is fed; bytes it has not reached yet are dropped
+    // with it. An error is yielded once and ends the iteration.
+    pub fn feed<'d, 'a>(&'d mut self, data: &'a [u8]) -> Feed<'d, 'a> {
+        Feed { decoder: self, data }
+    }
+
+    // Whether the decoder is between two frames, so the input may end here.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, State::Header) && self.header.is_empty()
+    }
+
+    // Signal the end of the input. Fails with the same error `deserialize_with`
+    // reports for a truncated frame if the input stopped part way through one.
+    pub fn finish(&self) -> Result<(), PalsError> {
+        match &self.state {
+            State::Header if self.header.is_empty() => Ok(()),
+            // A complete length table would already have been parsed, so this fails.
+            State::Header => self.config.read_header(&self.header).map(|_| ()),
+            State::Segment { index, remaining } => {
+                let len = self.lengths[*index];
+                Err(PalsError::TruncatedPayload {
+                    offset: self.offset - (len - remaining),
+                    segment: *index,
+                    expected: len,
+                    available: len - remaining,
+                })
+            }
+            State::Trailer => Err(PalsError::TruncatedPayload {
+                offset: self.offset - self.trailer.len(),
+                segment: self.lengths.len(),
+                expected: self.config.checksum.trailer_len(self.lengths.len()),
+                available: self.trailer.len(),
+            }),
+            State::Failed(e) => Err(e.clone()),
+        }
+    }
+
+    // Produce the next event from `data`, consuming the bytes it covers.
+    fn step<'a>(&mut self, data: &mut &'a [u8]) -> Option<Result<Event<'a>, PalsError>> {
+        loop {
+            match self.state {
+                State::Header => {
+                    if data.is_empty() {
+                        return None;
+                    }
+
+                    // Collect the current entry, one byte at a time for varints.
+                    let width = self.config.width();
+                    let take = (width - self.entry_len).min(data.len());
+                    self.header.extend_from_slice(&data[..take]);
+                    self.entry_len += take;
+                    self.offset += take;
+                    *data = &data[take..];
+
+                    if self.entry_len < width {
+                        continue;
+                    }
+                    self.entry_len = 0;
+
+                    // Stop at the terminator, or once the table is too long or a
+                    // varint too overlong to be valid; parsing reports what is wrong.
+                    let entry = &self.header[(self.header.len() - width)..];
+                    if entry.iter().any(|b| *b != 0) {
+                        // A varint entry ends with the first byte without the continuation bit.
+                        if self.config.width != Width::Varint || entry[0] & 0x80 == 0 {
+                            self.entries += 1;
+                        }
+
+                        // No valid varint has this byte after nine continued ones.
+                        let before = &self.header[..(self.header.len() - 1)];
+                        let tail = &before[before.len().saturating_sub(MAX_VARINT_LEN)..];
+                        let overlong = self.config.width == Width::Varint
+                            && tail.len() == MAX_VARINT_LEN
+                            && tail.iter().all(|b| *b & 0x80 != 0);
+
+                        if self.entries <= self.config.limits.max_segments && !overlong {
+                            continue;
+                        }
+                    }
+
+                    return Some(self.start_payload());
+                }
+                State::Segment { index, remaining } => {
+                    if remaining == 0 {
+                        if self.config.checksum.segments() {
+                            self.segment_crcs.push(self.segment_hasher.finish());
+                            self.segment_hasher = Crc32c::new();
+                        }
+                        self.next_segment(index + 1);
+                        return Some(Ok(Event::SegmentEnd { index }));
+                    }
+
+                    if data.is_empty() {
+                        return None;
+                    }
+
+                    let (bytes, rest) = data.split_at(remaining.min(data.len()));
+                    *data = rest;
+                    self.offset += bytes.len();
+                    self.state = State::Segment { index, remaining: remaining - bytes.len() };
+
+                    if self.config.checksum.segments() {
+                        self.segment_hasher.update(bytes);
+                    }
+                    if self.config.checksum.frame() {
+                        self.frame_hasher.update(bytes);
+                    }
+
+                    return Some(Ok(Event::SegmentChunk { index, bytes }));
+                }
+                State::Trailer => {
+                    let trailer_len = self.config.checksum.trailer_len(self.lengths.len());
+
+                    if self.trailer.len() < trailer_len {
+                        if data.is_empty() {
+                            return None;
+                        }
+
+                        let take = (trailer_len - self.trailer.len()).min(data.len());
+                        self.trailer.extend_from_slice(&data[..take]);
+                        self.offset += take;
+                        *data = &data[take..];
+                        continue;
+                    }
+
+                    return Some(self.finish_frame());
+                }
+                State::Failed(ref e) => {
+                    if data.is_empty() {
+                        return None;
+                    }
+
+                    *data = &[];
+                    return Some(Err(e.clone()));
+                }
+            }
+        }
+    }
+
+    // Parse the complete length table and move on to the payload.
+    fn start_payload<'a>(&mut self) -> Result<Event<'a>, PalsError> {
+        let lengths = match self.config.read_header(&self.header) {
+            Ok((lengths, _)) => lengths,
+            Err(e) => return Err(self.fail(e)),
+        };
+
+        if self.config.checksum.frame() {
+            self.frame_hasher.update(&self.header);
+        }
+
+        self.lengths = lengths.clone();
+        self.next_segment(0);
+
+        Ok(Event::Header { lengths })
+    }
+
+    fn next_segment(&mut self, index: usize) {
+        self.state = match self.lengths.get(index) {
+            Some(len) => State::Segment { index, remaining: *len },
+            None => State::Trailer,
+        };
+    }
+
+    // Check the trailer and get ready for the next frame.
+    fn finish_frame<'a>(&mut self) -> Result<Event<'a>, PalsError> {
+        let offset = self.offset - self.trailer.len();
+        let trailer = &self.trailer;
+        let frame_hasher = &mut self.frame_hasher;
+
+        let result = verify_trailer(self.config.checksum, self.segment_crcs.iter().copied(), trailer, offset, || {
+            frame_hasher.update(&trailer[..(trailer.len() - 4)]);
+            frame_hasher.finish()
+        });
+        if let Err(e) = result {
+            return Err(self.fail(e));
+        }
+
+        // Keep the buffers around for the next frame.
+        self.state = State::Header;
+        self.offset = 0;
+        self.header.clear();
+        self.entries = 0;
+        self.lengths.clear();
+        self.trailer.clear();
+        self.segment_crcs.clear();
+        self.frame_hasher = Crc32c::new();
+
+        Ok(Event::FrameEnd)
+    }
+
+    fn fail(&mut self, err: PalsError) -> PalsError {
+        self.state = State::Failed(err.clone());
+        err
+    }
+}
+
+// The events found in one chunk of input, see `PalsDecoder::feed`.
+pub struct Feed<'d, 'a> {
+    decoder: &'d mut PalsDecoder,
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Feed<'_, 'a> {
+    type Item = Result<Event<'a>, PalsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.decoder.step(&mut self.data);
+        if let Some(Err(_)) = event {
+            self.data = &[];
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_with, serialize_with, ByteOrder, Checksum, DecodeLimits};
+
+    // Feed `chunks` into a fresh decoder and rebuild the first frame from the
+    // events, checking that they come in a sensible order.
+    fn decode_chunks(config: PalsConfig, chunks: &[&[u8]]) -> Result<Vec<Vec<u8>>, PalsError> {
+        let mut decoder = PalsDecoder::with_config(config);
+        let mut lengths = None;
+        let mut segments: Vec<Vec<u8>> = Vec::new();
+
+        for chunk in chunks {
+            for event in decoder.feed(chunk) {
+                match event? {
+                    Event::Header { lengths: l } => {
+                        assert!(lengths.is_none());
+                        lengths = Some(l);
+                        segments.push(Vec::new());
+                    }
+                    Event::SegmentChunk { index, bytes } => {
+                        assert_eq!(index, segments.len() - 1);
+                        assert!(!bytes.is_empty());
+                        segments[index].extend_from_slice(bytes);
+                    }
+                    Event::SegmentEnd { index } => {
+                        assert_eq!(segments[index].len(), lengths.as_ref().unwrap()[index]);
+                        segments.push(Vec::new());
+                    }
+                    Event::FrameEnd => {
+                        segments.pop();
+                        assert_eq!(segments.len(), lengths.unwrap().len());
+                        return Ok(segments);
+                    }
+                }
+            }
+        }
+
+        decoder.finish()?;
+        unreachable!("the input ended cleanly before the first frame")
+    }
+
+    // Split `data` wherever `mask` has a bit set.
+    fn split(data: &[u8], mask: u64) -> Vec<&[u8]> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for i in 1..data.len() {
+            if mask & (1 << (i - 1)) != 0 {
+                chunks.push(&data[start..i]);
+                start = i;
+            }
+        }
+        chunks.push(&data[start..]);
+        chunks
+    }
+
+    fn configs() -> Vec<PalsConfig> {
+        let mut configs = vec![PalsConfig::LE, PalsConfig::BE, PalsConfig::VARINT];
+        for width in [Width::U16, Width::Varint] {
+            configs.push(PalsConfig::new(width, ByteOrder::Big).with_checksum(Checksum::Both));
+        }
+        configs.push(PalsConfig::LE.with_checksum(Checksum::Segments));
+        configs
+    }
+
+    #[test]
+    fn test_every_split_matches_deserialize() {
+        let frames: [&[&[u8]]; 4] = [&[b"ab", b"", b"c"], &[], &[b"", b""], &[b"hello"]];
+
+        for config in configs() {
+            for data in frames {
+                let serialized = serialize_with(&config, data).unwrap();
+                if serialized.len() > 18 {
+                    continue;
+                }
+
+                for mask in 0..(1u64 << (serialized.len() - 1)) {
+                    let chunks = split(&serialized, mask);
+                    assert_eq!(decode_chunks(config, &chunks).unwrap(), data, "{:?} {:?}", config, chunks);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_splits_match_deserialize() {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let payload: Vec<Vec<u8>> = (0..12).map(|i| vec![i as u8; i * 23]).collect();
+        let data: Vec<&[u8]> = payload.iter().map(|i| i.as_slice()).collect();
+
+        for config in configs().into_iter().filter(|i| i.width != Width::U8) {
+            let serialized = serialize_with(&config, &data).unwrap();
+
+            for _ in 0..200 {
+                let mask = next() & next();
+                let mut chunks = Vec::new();
+                let mut start = 0;
+                while start < serialized.len() {
+                    let len = (next() % 64 + (mask & 1)) as usize;
+                    let end = (start + len).min(serialized.len());
+                    chunks.push(&serialized[start..end]);
+                    start = end;
+                }
+
+                assert_eq!(decode_chunks(config, &chunks).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_and_corrupted_input_matches_deserialize() {
+        let limits = DecodeLimits { max_segments: 3, ..DecodeLimits::UNLIMITED };
+        let mut configs = configs();
+        configs.push(PalsConfig::VARINT.with_limits(limits));
+
+        for config in configs {
+            let serialized = serialize_with(&config, &[b"abc", b"", b"de"]).unwrap();
+
+            for cut in 0..serialized.len() {
+                let data = &serialized[..cut];
+                let expected = deserialize_with(&config, data).unwrap_err();
+
+                // With nothing fed there is no frame to report on.
+                if cut == 0 {
+                    assert!(PalsDecoder::with_config(config).finish().is_ok());
+                    continue;
+                }
+
+                for mask in [0, u64::MAX, 0x5555_5555] {
+                    let result = decode_chunks(config, &split(data, mask));
+                    assert_eq!(result, Err(expected.clone()), "{:?} cut at {}", config, cut);
+                }
+            }
+
+            for i in 0..serialized.len() {
+                for flip in [0x01, 0x80, 0xff] {
+                    let mut corrupted = serialized.clone();
+                    corrupted[i] ^= flip;
+                    // Let a corrupted length run past the frame without reading the next one.
+                    corrupted.extend_from_slice(&[0xff; 4]);
+
+                    let expected = deserialize_with(&config, &corrupted);
+                    for mask in [0, u64::MAX] {
+                        let result = decode_chunks(config, &split(&corrupted, mask));
+                        let result = result.map_err(|e| (e.is_incomplete(), e));
+                        let expected = expected.clone().map_err(|e| (e.is_incomplete(), e));
+
+                        // The stream decoder cannot tell a truncated frame from
+                        // one that is still arriving, so only compare outcomes that are final.
+                        if !matches!(expected, Err((true, _))) {
+                            assert_eq!(result, expected, "{:?} byte {} ^ {:#x}", config, i, flip);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_back_to_back_frames() {
+        let mut stream = serialize_with(&PalsConfig::LE, &[b"one"]).unwrap();
+        stream.extend(serialize_with(&PalsConfig::LE, &[]).unwrap());
+        stream.extend(serialize_with(&PalsConfig::LE, &[b"t", b"wo"]).unwrap());
+
+        let mut decoder = PalsDecoder::le();
+        let mut events = Vec::new();
+        for chunk in stream.chunks(3) {
+            events.extend(decoder.feed(chunk).map(Result::unwrap));
+        }
+
+        assert!(decoder.is_idle());
+        assert_eq!(decoder.finish(), Ok(()));
+        assert_eq!(
+            events,
+            [
+                Event::Header { lengths: vec![3] },
+                Event::SegmentChunk { index: 0, bytes: b"o" },
+                Event::SegmentChunk { index: 0, bytes: b"ne" },
+                Event::SegmentEnd { index: 0 },
+                Event::FrameEnd,
+                Event::Header { lengths: vec![] },
+                Event::FrameEnd,
+                Event::Header { lengths: vec![1, 2] },
+                Event::SegmentChunk { index: 0, bytes: b"t" },
+                Event::SegmentEnd { index: 0 },
+                Event::SegmentChunk { index: 1, bytes: b"wo" },
+                Event::SegmentEnd { index: 1 },
+                Event::FrameEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_decoder_stays_failed() {
+        let mut decoder = PalsDecoder::varint();
+
+        let mut events = decoder.feed(&[0x80, 0x00, 1, 2]);
+        assert!(matches!(events.next(), Some(Err(PalsError::InvalidVarint { offset: 0, .. }))));
+        assert!(events.next().is_none());
+
+        assert!(matches!(decoder.feed(&[0]).next(), Some(Err(PalsError::InvalidVarint { .. }))));
+        assert!(decoder.feed(&[]).next().is_none());
+        assert!(decoder.finish().is_err());
+        assert!(!decoder.is_idle());
+    }
+
+    #[test]
+    fn test_overlong_varint_fails_early() {
+        let mut decoder = PalsDecoder::varint();
+
+        let events: Vec<_> = decoder.feed(&[0x80; 10]).collect();
+        assert!(matches!(events[..], [Err(PalsError::InvalidVarint { offset: 0, expected: 9, available: 10, .. })]));
+    }
+}
@@ -0,0 +1,907 @@
+// Typed values on top of PALS framing.
+//
+// Every value is encoded into the bytes of a single segment, so it can be
+// stored as a field of a frame or on its own. The layout of each type is part
+// of the format and will not change:
+//
+//     bool                    1 byte, 0 or 1
+//     integers, floats        fixed width, big-endian
+//     usize, isize            as u64 and i64
+//     char                    its UTF-8 bytes
+//     str, String             the raw UTF-8 bytes
+//     ()                      no bytes
+//     Option                  [0] for None, [1] followed by the value for Some
+//     Vec, slices, arrays     elements of a fixed-width type (integers, floats,
+//                             bool and arrays of those) packed back to back,
+//                             so `Vec<u8>` is just its bytes; any other
+//                             element type as a nested frame with one segment
+//                             per element
+//     tuples                  a nested frame with one segment per element
+//     HashMap, BTreeMap       a nested frame with a key and a value segment
+//                             per entry, ordered by the encoded bytes of the
+//                             keys for HashMap and by key for BTreeMap, so the
+//                             same map always encodes the same way; decoding
+//                             rejects duplicate keys
+//
+// Nested frames use the `serialize_be` layout, the same one the derive macros
+// use for structs, so any of them can be taken apart with `deserialize_be`.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{decode_frame_exact, PalsConfig, PalsError};
+
+// Encode `value` into a new vector.
+pub fn encode<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    value.encode()
+}
+
+// Decode a value of type `T` from exactly the bytes of its encoding.
+// Strings and byte slices can borrow straight from `data`.
+pub fn decode<'a, T: Decode<'a>>(data: &'a [u8]) -> Result<T, PalsError> {
+    T::decode(data)
+}
+
+// A value that can be encoded into the bytes of a single segment.
+// Structs and enums get an implementation from `#[derive(PalsEncode)]`, which
+// lays them out as a `serialize_be` frame with one segment per field.
+pub trait Encode {
+    // The size of every encoding of this type, if it is always the same.
+    // Sequences of such values are packed without a length table.
+    const FIXED_LEN: Option<usize> = None;
+
+    // Append the encoding of `self` to `output`.
+    fn encode_to(&self, output: &mut Vec<u8>);
+
+    // Encode `self` into a new vector.
+    fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.encode_to(&mut output);
+        output
+    }
+}
+
+// A value that can be decoded from exactly the bytes of a single segment.
+// `'a` is the lifetime of the input, so decoded values can borrow from it.
+pub trait Decode<'a>: Sized {
+    // Must match `Encode::FIXED_LEN` of the same type.
+    const FIXED_LEN: Option<usize> = None;
+
+    // Decode a value from `data`, which must hold nothing but its encoding.
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError>;
+}
+
+// Writes a `serialize_be` frame with a known number of segments straight into
+// an output buffer. The length table is reserved up front and filled in as
+// each segment is encoded, so no segment has to be buffered on its own.
+pub struct FrameEncoder<'a> {
+    output: &'a mut Vec<u8>,
+    // Where the length table starts in `output`.
+    header: usize,
+    segments: usize,
+    index: usize,
+}
+
+impl<'a> FrameEncoder<'a> {
+    // Start a frame of `segments` segments at the end of `output`.
+    pub fn new(output: &'a mut Vec<u8>, segments: usize) -> Self {
+        let header = output.len();
+        output.resize(header + 8 * (segments + 1), 0); // The terminator is already in place.
+        FrameEncoder { output, header, segments, index: 0 }
+    }
+
+    // Encode `value` as the next segment. Writing more segments than were
+    // announced fails with `PalsError::SegmentCount` and writes nothing.
+    pub fn segment<T: Encode + ?Sized>(&mut self, value: &T) -> Result<(), PalsError> {
+        if self.index >= self.segments {
+            return Err(PalsError::SegmentCount {
+                offset: self.output.len(),
+                segment: self.index,
+                expected: self.segments,
+                available: self.index + 1,
+            });
+        }
+
+        let start = self.output.len();
+        value.encode_to(self.output);
+        let entry = 1 + (self.output.len() - start) as u64;
+
+        let slot = self.header + 8 * self.index;
+        self.output[slot..(slot + 8)].copy_from_slice(&entry.to_be_bytes());
+        self.index += 1;
+
+        Ok(())
+    }
+
+    // Finish the frame. If fewer segments were written than announced, the
+    // unfinished frame is removed from the output again and
+    // `PalsError::SegmentCount` is returned.
+    pub fn finish(self) -> Result<(), PalsError> {
+        if self.index != self.segments {
+            self.output.truncate(self.header);
+            return Err(PalsError::SegmentCount {
+                offset: self.header,
+                segment: self.index,
+                expected: self.segments,
+                available: self.index,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// Takes apart a `serialize_be` frame that fills its input completely and
+// decodes its segments one after another. Errors of a segment are reported
+// with offsets relative to the start of the frame.
+pub struct FrameDecoder<'a> {
+    data: &'a [u8],
+    segments: Vec<&'a [u8]>,
+    index: usize,
+}
+
+impl<'a> FrameDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, PalsError> {
+        let segments = decode_frame_exact(&PalsConfig::BE, data)?;
+        Ok(FrameDecoder { data, segments, index: 0 })
+    }
+
+    // The number of segments in the frame.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    // The number of segments that have not been decoded yet.
+    pub fn remaining(&self) -> usize {
+        self.segments.len() - self.index
+    }
+
+    // Decode the next segment as a `T`.
+    pub fn read<T: Decode<'a>>(&mut self) -> Result<T, PalsError> {
+        let (segment, offset) = self.take()?;
+        T::decode(segment).map_err(|e| e.shifted(offset))
+    }
+
+    // Decode the next segment as a `T`, or return `T::default()` if the frame
+    // has no segments left.
+    pub fn read_or_default<T: Decode<'a> + Default>(&mut self) -> Result<T, PalsError> {
+        if self.remaining() == 0 {
+            return Ok(T::default());
+        }
+        self.read()
+    }
+
+    // Decode the next segment as a `T`, after checking that it holds exactly
+    // one complete `serialize_be` frame.
+    pub fn read_nested<T: Decode<'a>>(&mut self) -> Result<T, PalsError> {
+        let (segment, offset) = self.take()?;
+        decode_frame_exact(&PalsConfig::BE, segment).map_err(|e| e.shifted(offset))?;
+        T::decode(segment).map_err(|e| e.shifted(offset))
+    }
+
+    // Check that every segment of the frame has been decoded.
+    pub fn finish(self) -> Result<(), PalsError> {
+        match self.segments.get(self.index) {
+            None => Ok(()),
+            Some(segment) => Err(PalsError::SegmentCount {
+                offset: self.offset_of(segment),
+                segment: self.index,
+                expected: self.index,
+                available: self.segments.len(),
+            }),
+        }
+    }
+
+    // Take the next segment along with its offset in the frame.
+    fn take(&mut self) -> Result<(&'a [u8], usize), PalsError> {
+        match self.segments.get(self.index) {
+            Some(segment) => {
+                self.index += 1;
+                Ok((segment, self.offset_of(segment)))
+            }
+            None => Err(PalsError::SegmentCount {
+                offset: self.data.len(),
+                segment: self.index,
+                expected: self.index + 1,
+                available: self.segments.len(),
+            }),
+        }
+    }
+
+    fn offset_of(&self, segment: &[u8]) -> usize {
+        segment.as_ptr() as usize - self.data.as_ptr() as usize
+    }
+}
+
+// Take `data` as a fixed-width value of `N` bytes.
+fn fixed<const N: usize>(data: &[u8]) -> Result<[u8; N], PalsError> {
+    data.try_into().map_err(|_| PalsError::InvalidLength { offset: 0, segment: 0, expected: N, available: data.len() })
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    const FIXED_LEN: Option<usize> = T::FIXED_LEN;
+
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        (**self).encode_to(output)
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    const FIXED_LEN: Option<usize> = T::FIXED_LEN;
+
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        (**self).encode_to(output)
+    }
+}
+
+impl<'a, T: Decode<'a>> Decode<'a> for Box<T> {
+    const FIXED_LEN: Option<usize> = T::FIXED_LEN;
+
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        T::decode(data).map(Box::new)
+    }
+}
+
+// Integers and floats are stored as fixed-width big-endian values.
+macro_rules! impl_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                const FIXED_LEN: Option<usize> = Some(core::mem::size_of::<$ty>());
+
+                fn encode_to(&self, output: &mut Vec<u8>) {
+                    output.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+
+            impl Decode<'_> for $ty {
+                const FIXED_LEN: Option<usize> = Some(core::mem::size_of::<$ty>());
+
+                fn decode(data: &[u8]) -> Result<Self, PalsError> {
+                    Ok(<$ty>::from_be_bytes(fixed(data)?))
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+// Pointer-sized integers are stored as 64-bit ones, so the layout does not
+// depend on the platform.
+macro_rules! impl_size {
+    ($($ty:ty as $wide:ty),*) => {
+        $(
+            impl Encode for $ty {
+                const FIXED_LEN: Option<usize> = Some(8);
+
+                fn encode_to(&self, output: &mut Vec<u8>) {
+                    (*self as $wide).encode_to(output)
+                }
+            }
+
+            impl Decode<'_> for $ty {
+                const FIXED_LEN: Option<usize> = Some(8);
+
+                fn decode(data: &[u8]) -> Result<Self, PalsError> {
+                    let value = <$wide>::decode(data)?;
+                    <$ty>::try_from(value).map_err(|_| PalsError::InvalidValue {
+                        offset: 0,
+                        segment: 0,
+                        expected: <$ty>::MAX as usize,
+                        available: value as usize,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_size!(usize as u64, isize as i64);
+
+impl Encode for bool {
+    const FIXED_LEN: Option<usize> = Some(1);
+
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        output.push(*self as u8);
+    }
+}
+
+impl Decode<'_> for bool {
+    const FIXED_LEN: Option<usize> = Some(1);
+
+    fn decode(data: &[u8]) -> Result<Self, PalsError> {
+        match fixed(data)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            [tag] => Err(PalsError::InvalidValue { offset: 0, segment: 0, expected: 1, available: tag as usize }),
+        }
+    }
+}
+
+impl Encode for char {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+}
+
+impl Decode<'_> for char {
+    fn decode(data: &[u8]) -> Result<Self, PalsError> {
+        let mut chars = <&str>::decode(data)?.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            (c, _) => Err(PalsError::InvalidLength {
+                offset: 0,
+                segment: 0,
+                expected: c.map_or(1, char::len_utf8),
+                available: data.len(),
+            }),
+        }
+    }
+}
+
+impl Encode for () {
+    fn encode_to(&self, _output: &mut Vec<u8>) {}
+}
+
+impl Decode<'_> for () {
+    fn decode(data: &[u8]) -> Result<Self, PalsError> {
+        fixed::<0>(data).map(|_| ())
+    }
+}
+
+// Strings are stored as their raw bytes.
+impl Encode for str {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl<'a, 'b: 'a> Decode<'b> for &'a str {
+    fn decode(data: &'b [u8]) -> Result<Self, PalsError> {
+        core::str::from_utf8(data).map_err(|e| PalsError::InvalidValue {
+            offset: e.valid_up_to(),
+            segment: 0,
+            expected: e.valid_up_to(),
+            available: data.len(),
+        })
+    }
+}
+
+impl Decode<'_> for String {
+    fn decode(data: &[u8]) -> Result<Self, PalsError> {
+        <&str>::decode(data).map(String::from)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        match self {
+            None => output.push(0),
+            Some(value) => {
+                output.push(1);
+                value.encode_to(output);
+            }
+        }
+    }
+}
+
+impl<'a, T: Decode<'a>> Decode<'a> for Option<T> {
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        match data.split_first() {
+            Some((0, [])) => Ok(None),
+            Some((1, rest)) => T::decode(rest).map(Some).map_err(|e| e.shifted(1)),
+            Some((0, _)) => Err(PalsError::InvalidLength { offset: 0, segment: 0, expected: 1, available: data.len() }),
+            Some((tag, _)) => Err(PalsError::InvalidValue { offset: 0, segment: 0, expected: 1, available: *tag as usize }),
+            None => Err(PalsError::InvalidLength { offset: 0, segment: 0, expected: 1, available: 0 }),
+        }
+    }
+}
+
+// The width elements of type `T` are packed with, if they can be packed.
+// Zero-sized elements are framed, so their number is not lost.
+const fn packed_width(fixed_len: Option<usize>) -> Option<usize> {
+    match fixed_len {
+        Some(width) if width > 0 => Some(width),
+        _ => None,
+    }
+}
+
+// Encode the `len` elements yielded by `iter` as a sequence.
+fn encode_seq<'a, T: Encode + 'a>(iter: impl Iterator<Item = &'a T>, len: usize, output: &mut Vec<u8>) {
+    if packed_width(T::FIXED_LEN).is_some() {
+        iter.for_each(|i| i.encode_to(output));
+        return;
+    }
+
+    // `len` is the exact length of the collection, so the frame cannot be miscounted.
+    let mut frame = FrameEncoder::new(output, len);
+    iter.for_each(|i| {
+        let _ = frame.segment(i);
+    });
+    let _ = frame.finish();
+}
+
+// Decode a sequence written by `encode_seq`.
+fn decode_seq<'a, T: Decode<'a>>(data: &'a [u8]) -> Result<Vec<T>, PalsError> {
+    if let Some(width) = packed_width(T::FIXED_LEN) {
+        if data.len() % width != 0 {
+            return Err(PalsError::InvalidLength {
+                offset: data.len() - data.len() % width,
+                segment: 0,
+                expected: width,
+                available: data.len() % width,
+            });
+        }
+
+        let chunks = data.chunks_exact(width).enumerate();
+        return chunks.map(|(index, i)| T::decode(i).map_err(|e| e.shifted(index * width))).collect();
+    }
+
+    let mut frame = FrameDecoder::new(data)?;
+    let mut output = Vec::with_capacity(frame.len());
+    while frame.remaining() > 0 {
+        output.push(frame.read()?);
+    }
+    Ok(output)
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        encode_seq(self.iter(), self.len(), output);
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        encode_seq(self.iter(), self.len(), output);
+    }
+}
+
+impl<'a, T: Decode<'a>> Decode<'a> for Vec<T> {
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        decode_seq(data)
+    }
+}
+
+// Byte slices can borrow from the input, as their packed layout is the input.
+impl<'a, 'b: 'a> Decode<'b> for &'a [u8] {
+    fn decode(data: &'b [u8]) -> Result<Self, PalsError> {
+        Ok(data)
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    const FIXED_LEN: Option<usize> = match T::FIXED_LEN {
+        Some(width) => Some(width * N),
+        None => None,
+    };
+
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        encode_seq(self.iter(), N, output);
+    }
+}
+
+impl<'a, T: Decode<'a>, const N: usize> Decode<'a> for [T; N] {
+    const FIXED_LEN: Option<usize> = match T::FIXED_LEN {
+        Some(width) => Some(width * N),
+        None => None,
+    };
+
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        let elements = decode_seq::<T>(data)?;
+        let len = elements.len();
+
+        elements.try_into().map_err(|_| match packed_width(T::FIXED_LEN) {
+            Some(width) => PalsError::InvalidLength { offset: 0, segment: 0, expected: width * N, available: data.len() },
+            None => PalsError::SegmentCount { offset: 0, segment: 0, expected: N, available: len },
+        })
+    }
+}
+
+// Tuples are framed like a struct with one field per element.
+macro_rules! impl_tuple {
+    ($len:expr => $($name:ident),+) => {
+        impl<$($name: Encode),+> Encode for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode_to(&self, output: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                // The tuple fixes the number of segments, so neither call can fail.
+                let mut frame = FrameEncoder::new(output, $len);
+                $(let _ = frame.segment($name);)+
+                let _ = frame.finish();
+            }
+        }
+
+        impl<'a, $($name: Decode<'a>),+> Decode<'a> for ($($name,)+) {
+            fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+                let mut frame = FrameDecoder::new(data)?;
+                let value = ($(frame.read::<$name>()?,)+);
+                frame.finish()?;
+                Ok(value)
+            }
+        }
+    };
+}
+
+impl_tuple!(1 => A);
+impl_tuple!(2 => A, B);
+impl_tuple!(3 => A, B, C);
+impl_tuple!(4 => A, B, C, D);
+impl_tuple!(5 => A, B, C, D, E);
+impl_tuple!(6 => A, B, C, D, E, F);
+impl_tuple!(7 => A, B, C, D, E, F, G);
+impl_tuple!(8 => A, B, C, D, E, F, G, H);
+impl_tuple!(9 => A, B, C, D, E, F, G, H, I);
+impl_tuple!(10 => A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(11 => A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(12 => A, B, C, D, E, F, G, H, I, J, K, L);
+
+// Encode the `len` entries yielded by `iter` as a map.
+fn encode_map<'a, K: Encode + ?Sized + 'a, V: Encode + 'a>(
+    iter: impl Iterator<Item = (&'a K, &'a V)>,
+    len: usize,
+    output: &mut Vec<u8>,
+) {
+    // `len` is the exact length of the map, so the frame cannot be miscounted.
+    let mut frame = FrameEncoder::new(output, 2 * len);
+    for (key, value) in iter {
+        let _ = frame.segment(key);
+        let _ = frame.segment(value);
+    }
+    let _ = frame.finish();
+}
+
+// Decode the entries of a map written by `encode_map`, handing each one to
+// `insert`, which returns false if the map already held its key. Duplicate
+// keys are rejected rather than letting the last one win.
+fn decode_map<'a, K: Decode<'a>, V: Decode<'a>>(
+    data: &'a [u8],
+    mut insert: impl FnMut(K, V) -> bool,
+) -> Result<(), PalsError> {
+    let mut frame = FrameDecoder::new(data)?;
+
+    if frame.len() % 2 != 0 {
+        return Err(PalsError::SegmentCount { offset: 0, segment: 0, expected: frame.len() + 1, available: frame.len() });
+    }
+
+    while frame.remaining() > 0 {
+        let segment = frame.index;
+        let offset = frame.offset_of(frame.segments[segment]);
+
+        let (key, value) = (frame.read()?, frame.read()?);
+        if !insert(key, value) {
+            // The map should have grown to `segment / 2 + 1` entries, but the key was already there.
+            return Err(PalsError::InvalidValue { offset, segment, expected: segment / 2 + 1, available: segment / 2 });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+impl<K: Encode, V: Encode, S> Encode for HashMap<K, V, S> {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        // The iteration order of a `HashMap` changes between runs, so sort the
+        // entries by their encoded keys to make the encoding canonical.
+        let mut entries: Vec<(Vec<u8>, &V)> = self.iter().map(|(key, value)| (key.encode(), value)).collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        encode_map(entries.iter().map(|(key, value)| (key.as_slice(), *value)), entries.len(), output);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: Decode<'a> + Eq + Hash, V: Decode<'a>, S: BuildHasher + Default> Decode<'a> for HashMap<K, V, S> {
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        let mut output = HashMap::default();
+        decode_map(data, |key, value| output.insert(key, value).is_none())?;
+        Ok(output)
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode_to(&self, output: &mut Vec<u8>) {
+        encode_map(self.iter(), self.len(), output);
+    }
+}
+
+impl<'a, K: Decode<'a> + Ord, V: Decode<'a>> Decode<'a> for BTreeMap<K, V> {
+    fn decode(data: &'a [u8]) -> Result<Self, PalsError> {
+        let mut output = BTreeMap::new();
+        decode_map(data, |key, value| output.insert(key, value).is_none())?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_be, serialize_be};
+
+    #[test]
+    fn test_encode_tuple() {
+        let encoded = encode(&(42u32, "name", vec![1u8, 2]));
+
+        assert_eq!(encoded, serialize_be(&[&[0, 0, 0, 42], b"name", &[1, 2]]).unwrap());
+        assert_eq!(decode::<(u32, &str, Vec<u8>)>(&encoded).unwrap(), (42, "name", vec![1, 2]));
+    }
+
+    #[test]
+    fn test_round_trip_values() {
+        fn round_trip<T: Encode + for<'a> Decode<'a> + PartialEq + std::fmt::Debug>(value: T) {
+            assert_eq!(decode::<T>(&encode(&value)).unwrap(), value);
+        }
+
+        round_trip(-5i64);
+        round_trip(u128::MAX);
+        round_trip(1.5f32);
+        round_trip(-0.25f64);
+        round_trip(usize::MAX);
+        round_trip(true);
+        round_trip('ß');
+        round_trip(());
+        round_trip(String::from("text"));
+        round_trip(Some(Some(3u8)));
+        round_trip(None::<String>);
+        round_trip(vec![String::from("a"), String::new(), String::from("c")]);
+        round_trip(vec![vec![1u16, 2], vec![], vec![3]]);
+        round_trip(vec![(); 3]);
+        round_trip([[1u8, 2], [3, 4]]);
+        round_trip([String::from("x"), String::from("y")]);
+        round_trip((1u8, 2i8, 3u16, 4i16, 5u32, 6i32, 7u64, 8i64, 9u128, 10i128, 'k', false));
+        round_trip([(1u8, String::from("a")), (2, String::from("b"))].into_iter().collect::<BTreeMap<_, _>>());
+        #[cfg(feature = "std")]
+        round_trip([(String::from("a"), vec![1u8])].into_iter().collect::<HashMap<_, _>>());
+        round_trip(Box::new(7u8));
+    }
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(encode(&0x0102u16), [1, 2]);
+        assert_eq!(encode(&-1i8), [0xff]);
+        assert_eq!(encode(&1.0f32), 1.0f32.to_be_bytes());
+        assert_eq!(encode(&7usize), [0, 0, 0, 0, 0, 0, 0, 7]);
+        assert_eq!(encode(&Some(3u8)), [1, 3]);
+        assert_eq!(encode(&None::<u8>), [0]);
+        assert_eq!(encode(&vec![1u16, 2]), [0, 1, 0, 2]);
+        assert_eq!(encode(&[true, false]), [1, 0]);
+        assert_eq!(encode(&vec!["ab", "c"]), serialize_be(&[b"ab", b"c"]).unwrap());
+
+        let map: BTreeMap<&str, u8> = [("b", 2), ("a", 1)].into_iter().collect();
+        assert_eq!(deserialize_be(&encode(&map)).unwrap(), [b"a".to_vec(), vec![1], b"b".to_vec(), vec![2]]);
+    }
+
+    #[test]
+    fn test_frame_encoder_miscount() {
+        let mut output = vec![9];
+        let mut frame = FrameEncoder::new(&mut output, 1);
+        frame.segment(&1u32).unwrap();
+        assert!(matches!(frame.segment(&2u32), Err(PalsError::SegmentCount { segment: 1, expected: 1, .. })));
+        frame.finish().unwrap();
+        assert_eq!(output, [[9].as_slice(), &serialize_be(&[&[0, 0, 0, 1]]).unwrap()].concat());
+
+        let mut output = vec![9];
+        let mut frame = FrameEncoder::new(&mut output, 2);
+        frame.segment(&b"a"[..]).unwrap();
+        assert!(matches!(frame.finish(), Err(PalsError::SegmentCount { offset: 1, expected: 2, available: 1, .. })));
+        assert_eq!(output, [9]);
+    }
+
+    #[test]
+    fn test_frame_decoder() {
+        let encoded = serialize_be(&[&[0, 0, 0, 7], b"body"]).unwrap();
+        let mut frame = FrameDecoder::new(&encoded).unwrap();
+
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame.read::<u32>().unwrap(), 7);
+        assert_eq!(frame.read::<&[u8]>().unwrap(), b"body");
+        assert_eq!(frame.read_or_default::<Vec<u8>>().unwrap(), Vec::<u8>::new());
+        assert!(matches!(frame.read::<u32>(), Err(PalsError::SegmentCount { offset: 32, segment: 2, .. })));
+        frame.finish().unwrap();
+
+        let mut frame = FrameDecoder::new(&encoded).unwrap();
+        assert!(matches!(frame.read::<&[u8]>(), Ok(&[0, 0, 0, 7])));
+        assert!(matches!(frame.finish(), Err(PalsError::SegmentCount { offset: 28, segment: 1, .. })));
+    }
+
+    #[test]
+    fn test_borrowed_values_point_into_input() {
+        let encoded = encode(&vec!["one", "two"]);
+
+        let decoded: Vec<&str> = decode(&encoded).unwrap();
+
+        assert_eq!(decoded, ["one", "two"]);
+        assert!(encoded.as_ptr_range().contains(&decoded[1].as_ptr()));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode::<u32>(&[1, 2, 3]), Err(PalsError::InvalidLength { expected: 4, available: 3, .. })));
+        assert!(matches!(decode::<bool>(&[2]), Err(PalsError::InvalidValue { available: 2, .. })));
+        assert!(matches!(decode::<String>(b"ab\xff"), Err(PalsError::InvalidValue { offset: 2, .. })));
+        assert!(matches!(decode::<char>(b"ab"), Err(PalsError::InvalidLength { expected: 1, available: 2, .. })));
+        assert!(matches!(decode::<Option<u8>>(&[2, 0]), Err(PalsError::InvalidValue { .. })));
+        assert!(matches!(decode::<Option<u16>>(&[1, 0]), Err(PalsError::InvalidLength { offset: 1, .. })));
+        assert!(matches!(
+            decode::<Vec<u16>>(&[0, 1, 0]),
+            Err(PalsError::InvalidLength { offset: 2, expected: 2, available: 1, .. })
+        ));
+        assert!(matches!(decode::<Vec<bool>>(&[1, 0, 5]), Err(PalsError::InvalidValue { offset: 2, .. })));
+        assert!(matches!(decode::<[u8; 3]>(&[1, 2]), Err(PalsError::InvalidLength { expected: 3, available: 2, .. })));
+        assert!(matches!(decode::<(u8, u8)>(&encode(&(1u8, 2u8, 3u8))), Err(PalsError::SegmentCount { .. })));
+
+        let odd = serialize_be(&[b"a", b"b", b"c"]).unwrap();
+        assert!(matches!(decode::<BTreeMap<String, String>>(&odd), Err(PalsError::SegmentCount { .. })));
+    }
+
+    #[test]
+    fn test_maps_reject_duplicate_keys() {
+        let duplicate = serialize_be(&[b"a", b"1", b"b", b"2", b"a", b"3"]).unwrap();
+
+        assert!(matches!(
+            decode::<BTreeMap<&str, &str>>(&duplicate),
+            Err(PalsError::InvalidValue { offset: 60, segment: 4, .. })
+        ));
+        #[cfg(feature = "std")]
+        assert!(matches!(
+            decode::<HashMap<&str, &str>>(&duplicate),
+            Err(PalsError::InvalidValue { offset: 60, segment: 4, .. })
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hash_map_encoding_is_canonical() {
+        let map: HashMap<String, u8> = (0..50u8).map(|i| (i.to_string(), i)).collect();
+        let btree: BTreeMap<Vec<u8>, u8> = map.iter().map(|(key, value)| (key.encode(), *value)).collect();
+
+        // Built with another random hash seed, the copy iterates in a different order.
+        let copy: HashMap<String, u8> = map.clone().into_iter().collect();
+
+        assert_eq!(encode(&map), encode(&copy));
+        assert_eq!(encode(&map), encode(&btree));
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod derive_tests {
+    use super::*;
+    use crate::{deserialize_be, serialize_be, PalsDecode, PalsEncode};
+
+    #[derive(Debug, PartialEq, PalsEncode, PalsDecode)]
+    struct Message<'a> {
+        id: u32,
+        name: Vec<u8>,
+        body: &'a [u8],
+        #[pals(skip)]
+        cache: Vec<u8>,
+        #[pals(nested)]
+        header: Header,
+        #[pals(default)]
+        retries: u32,
+    }
+
+    #[derive(Debug, PartialEq, Default, PalsEncode, PalsDecode)]
+    struct Header(u32, Vec<u8>);
+
+    #[derive(Debug, PartialEq, PalsEncode, PalsDecode)]
+    enum Kind<T> {
+        Empty,
+        Tuple(u32, T),
+        Struct { a: u32, b: Vec<u8> },
+    }
+
+    fn message() -> Message<'static> {
+        Message {
+            id: 42,
+            name: b"name".to_vec(),
+            body: b"body",
+            cache: vec![],
+            header: Header(7, vec![1]),
+            retries: 3,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_struct() {
+        let encoded = message().encode();
+
+        assert_eq!(Message::decode(&encoded).unwrap(), message());
+    }
+
+    #[test]
+    fn test_struct_layout_matches_serialize_be() {
+        let encoded = message().encode();
+
+        let header = serialize_be(&[&[0, 0, 0, 7], &[1]]).unwrap();
+        let expected = serialize_be(&[&[0, 0, 0, 42], b"name", b"body", &header, &[0, 0, 0, 3]]).unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(deserialize_be(&encoded).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_round_trip_enum() {
+        for kind in [Kind::Empty, Kind::Tuple(1, b"x".to_vec()), Kind::Struct { a: 1, b: vec![2, 3] }] {
+            let encoded = kind.encode();
+
+            assert_eq!(Kind::decode(&encoded).unwrap(), kind);
+        }
+
+        let encoded = Kind::<Vec<u8>>::Tuple(5, b"x".to_vec()).encode();
+        assert_eq!(encoded, serialize_be(&[&[0, 0, 0, 1], &[0, 0, 0, 5], b"x"]).unwrap());
+    }
+
+    #[test]
+    fn test_skip_and_default_fields() {
+        let mut original = message();
+        original.cache = vec![1, 2, 3];
+
+        let encoded = original.encode();
+        let decoded = Message::decode(&encoded).unwrap();
+        assert_eq!(decoded.cache, Vec::<u8>::new());
+
+        // A frame written before `retries` existed still decodes.
+        let header = serialize_be(&[&[0, 0, 0, 7], &[1]]).unwrap();
+        let old = serialize_be(&[&[0, 0, 0, 42], b"name", b"body", &header]).unwrap();
+        assert_eq!(Message::decode(&old).unwrap().retries, 0);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let encoded = serialize_be(&[&[0, 0, 42], b"name"]).unwrap();
+        assert!(matches!(
+            Message::decode(&encoded),
+            Err(PalsError::InvalidLength { offset: 24, segment: 0, expected: 4, available: 3 })
+        ));
+
+        let encoded = serialize_be(&[&[0, 0, 0, 42], b"name"]).unwrap();
+        assert!(matches!(Message::decode(&encoded), Err(PalsError::SegmentCount { segment: 2, expected: 3, .. })));
+
+        let mut encoded = message().encode();
+        encoded.push(0);
+        assert!(matches!(Message::decode(&encoded), Err(PalsError::TrailingBytes { .. })));
+
+        let encoded = serialize_be(&[&[0, 0, 0, 3]]).unwrap();
+        assert!(matches!(
+            Kind::<Vec<u8>>::decode(&encoded),
+            Err(PalsError::InvalidValue { expected: 3, available: 3, .. })
+        ));
+
+        let encoded = serialize_be(&[&[0, 0, 0, 0], &[1]]).unwrap();
+        assert!(matches!(Kind::<Vec<u8>>::decode(&encoded), Err(PalsError::SegmentCount { segment: 1, .. })));
+    }
+
+    #[test]
+    fn test_nested_field_must_be_a_frame() {
+        let encoded = serialize_be(&[&[0, 0, 0, 42], b"name", b"body", b"oops"]).unwrap();
+
+        assert!(matches!(Message::decode(&encoded), Err(PalsError::MissingTerminator { .. })));
+    }
+
+    #[test]
+    fn test_borrowed_fields_point_into_input() {
+        let encoded = message().encode();
+
+        let decoded = Message::decode(&encoded).unwrap();
+
+        assert!(encoded.as_ptr_range().contains(&decoded.body.as_ptr()));
+    }
+}
@@ -0,0 +1,310 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+// The error type returned by every serializer and deserializer in this crate.
+// Each variant carries enough context to tell where in the frame things went
+// wrong and how many bytes were missing, so callers can decide whether to
+// wait for more data or give up on the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PalsError {
+    // There were no segments to serialize, and the config rejects empty frames.
+    EmptyInput {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A segment is longer than the length table of the format can express.
+    SegmentTooLarge {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The length table ended before the zero terminator was found.
+    MissingTerminator {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The length table is complete, but the payload is shorter than it claims.
+    TruncatedPayload {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A length (or the sum of all lengths) does not fit into a usize.
+    LengthOverflow {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A varint length is overlong, out of range or not in its shortest form.
+    InvalidVarint {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The input ended before the end of the preamble.
+    TruncatedPreamble {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The input does not start with the PALS magic bytes.
+    BadMagic {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The preamble announces a newer format version than this crate knows.
+    // `expected` is the newest supported version, `available` the one found.
+    UnsupportedVersion {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The preamble uses layout flags or feature bits this crate does not know.
+    // `expected` holds the known bits, `available` the ones found.
+    UnsupportedFlags {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The checksum of a segment does not match its contents.
+    // `expected` holds the stored checksum, `available` the computed one.
+    SegmentChecksumMismatch {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The checksum over the whole frame does not match its contents.
+    // `expected` holds the stored checksum, `available` the computed one.
+    FrameChecksumMismatch {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A fixed-width value was decoded from a segment of the wrong size.
+    InvalidLength {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A frame has a different number of segments than the decoded type needs.
+    SegmentCount {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A segment holds bytes that are not a valid value of the decoded type,
+    // such as a bool other than 0 or 1, invalid UTF-8 or an unknown variant.
+    InvalidValue {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // Bytes were left over after the end of a frame that must fill its input.
+    TrailingBytes {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The frame has more segments than `DecodeLimits::max_segments`.
+    // `expected` holds the limit, `available` the number of segments seen so far.
+    TooManySegments {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // A segment is longer than `DecodeLimits::max_segment_len`.
+    SegmentOverLimit {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The segments add up to more than `DecodeLimits::max_total_len`.
+    PayloadOverLimit {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The last of a run of back-to-back frames was cut short, as after a crash
+    // during an append. `offset` is where that frame starts, so everything
+    // before it is intact; the other fields describe what was missing.
+    TornTail {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+    // The buffer given to a `serialize_*_into` function cannot hold the frame.
+    // `expected` holds the size of the frame, `available` the size of the buffer.
+    BufferTooSmall {
+        offset: usize,
+        segment: usize,
+        expected: usize,
+        available: usize,
+    },
+}
+
+impl PalsError {
+    // The byte offset in the frame at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.fields().0
+    }
+
+    // The index of the segment the error refers to.
+    pub fn segment(&self) -> usize {
+        self.fields().1
+    }
+
+    // The number of bytes that were needed.
+    pub fn expected(&self) -> usize {
+        self.fields().2
+    }
+
+    // The number of bytes that were actually available.
+    pub fn available(&self) -> usize {
+        self.fields().3
+    }
+
+    // Returns true if the input may still turn into a valid frame once more
+    // bytes arrive, i.e. it was cut short rather than malformed.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            PalsError::MissingTerminator { .. }
+                | PalsError::TruncatedPayload { .. }
+                | PalsError::TruncatedPreamble { .. }
+                | PalsError::TornTail { .. }
+        )
+    }
+
+    // Move the offset of the error by `by` bytes, for errors found in a frame
+    // that does not start at the beginning of the input.
+    pub(crate) fn shifted(mut self, by: usize) -> Self {
+        match &mut self {
+            PalsError::EmptyInput { offset, .. }
+            | PalsError::SegmentTooLarge { offset, .. }
+            | PalsError::MissingTerminator { offset, .. }
+            | PalsError::TruncatedPayload { offset, .. }
+            | PalsError::LengthOverflow { offset, .. }
+            | PalsError::InvalidVarint { offset, .. }
+            | PalsError::TruncatedPreamble { offset, .. }
+            | PalsError::BadMagic { offset, .. }
+            | PalsError::UnsupportedVersion { offset, .. }
+            | PalsError::UnsupportedFlags { offset, .. }
+            | PalsError::SegmentChecksumMismatch { offset, .. }
+            | PalsError::FrameChecksumMismatch { offset, .. }
+            | PalsError::InvalidLength { offset, .. }
+            | PalsError::SegmentCount { offset, .. }
+            | PalsError::InvalidValue { offset, .. }
+            | PalsError::TrailingBytes { offset, .. }
+            | PalsError::TooManySegments { offset, .. }
+            | PalsError::SegmentOverLimit { offset, .. }
+            | PalsError::PayloadOverLimit { offset, .. }
+            | PalsError::TornTail { offset, .. }
+            | PalsError::BufferTooSmall { offset, .. } => *offset += by,
+        }
+        self
+    }
+
+    // The offset, segment, expected and available fields shared by every variant.
+    fn fields(&self) -> (usize, usize, usize, usize) {
+        match *self {
+            PalsError::EmptyInput { offset, segment, expected, available }
+            | PalsError::SegmentTooLarge { offset, segment, expected, available }
+            | PalsError::MissingTerminator { offset, segment, expected, available }
+            | PalsError::TruncatedPayload { offset, segment, expected, available }
+            | PalsError::LengthOverflow { offset, segment, expected, available }
+            | PalsError::InvalidVarint { offset, segment, expected, available }
+            | PalsError::TruncatedPreamble { offset, segment, expected, available }
+            | PalsError::BadMagic { offset, segment, expected, available }
+            | PalsError::UnsupportedVersion { offset, segment, expected, available }
+            | PalsError::UnsupportedFlags { offset, segment, expected, available }
+            | PalsError::SegmentChecksumMismatch { offset, segment, expected, available }
+            | PalsError::FrameChecksumMismatch { offset, segment, expected, available }
+            | PalsError::InvalidLength { offset, segment, expected, available }
+            | PalsError::SegmentCount { offset, segment, expected, available }
+            | PalsError::InvalidValue { offset, segment, expected, available }
+            | PalsError::TrailingBytes { offset, segment, expected, available }
+            | PalsError::TooManySegments { offset, segment, expected, available }
+            | PalsError::SegmentOverLimit { offset, segment, expected, available }
+            | PalsError::PayloadOverLimit { offset, segment, expected, available }
+            | PalsError::TornTail { offset, segment, expected, available }
+            | PalsError::BufferTooSmall { offset, segment, expected, available } => {
+                (offset, segment, expected, available)
+            }
+        }
+    }
+}
+
+impl fmt::Display for PalsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            PalsError::EmptyInput { .. } => "Input data is empty.",
+            PalsError::SegmentTooLarge { .. } => "Input data contains a slice that is too large to be serialized.",
+            PalsError::MissingTerminator { .. } => "Input data is missing the terminating null byte.",
+            PalsError::TruncatedPayload { .. } => "Input data is incomplete.",
+            PalsError::LengthOverflow { .. } => "Input data contains a length that does not fit into memory.",
+            PalsError::InvalidVarint { .. } => "Input data contains an overlong or non-canonical varint length.",
+            PalsError::TruncatedPreamble { .. } => "Input data is missing part of the preamble.",
+            PalsError::BadMagic { .. } => "Input data does not start with the PALS magic bytes.",
+            PalsError::UnsupportedVersion { .. } => "Input data uses an unsupported format version.",
+            PalsError::UnsupportedFlags { .. } => "Input data uses unsupported format flags.",
+            PalsError::SegmentChecksumMismatch { .. } => "Input data contains a corrupted segment.",
+            PalsError::FrameChecksumMismatch { .. } => "Input data contains a corrupted frame.",
+            PalsError::InvalidLength { .. } => "Input data contains a value of the wrong size.",
+            PalsError::SegmentCount { .. } => "Input data has an unexpected number of segments.",
+            PalsError::InvalidValue { .. } => "Input data contains an invalid value.",
+            PalsError::TrailingBytes { .. } => "Input data continues after the end of the frame.",
+            PalsError::TooManySegments { .. } => "Input data has more segments than the decode limits allow.",
+            PalsError::SegmentOverLimit { .. } => "Input data contains a segment longer than the decode limits allow.",
+            PalsError::PayloadOverLimit { .. } => "Input data has a larger payload than the decode limits allow.",
+            PalsError::TornTail { .. } => "Input data ends with an incomplete frame.",
+            PalsError::BufferTooSmall { .. } => "Output buffer is too small to hold the frame.",
+        };
+
+        write!(
+            f,
+            "{} (offset {}, segment {}, expected {} bytes, {} available)",
+            message,
+            self.offset(),
+            self.segment(),
+            self.expected(),
+            self.available()
+        )
+    }
+}
+
+impl core::error::Error for PalsError {}
+
+// Lets `PalsError` travel through `std::io` based APIs such as `PalsReader`.
+// Frames that were cut short become `UnexpectedEof`, anything else `InvalidData`.
+#[cfg(feature = "std")]
+impl From<PalsError> for io::Error {
+    fn from(err: PalsError) -> io::Error {
+        let kind = if err.is_incomplete() { io::ErrorKind::UnexpectedEof } else { io::ErrorKind::InvalidData };
+        io::Error::new(kind, err)
+    }
+}
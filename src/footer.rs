@@ -0,0 +1,521 @@
+// A variant of the format with the length table at the end, for writers that
+// only learn how long a segment is once they have written it, such as a
+// recorder streaming a capture straight to disk:
+//
+//     segments        the payload, every segment back to back
+//     length table    the `serialize_be` length table: a big-endian u64 per
+//                     segment holding its length + 1, then 8 zero bytes
+//     trailer         16 bytes: the offset of the length table as a
+//                     big-endian u64, followed by the magic bytes "PALSFOOT"
+//
+// Readers start from the fixed-size trailer at the end of the input, so the
+// layout suits files and other seekable sources rather than pipes.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+#[cfg(feature = "std")]
+use crate::DecodeLimits;
+use crate::{decode_frame_exact, PalsConfig, PalsError};
+
+// The magic bytes that end every footer-indexed frame.
+const MAGIC: [u8; 8] = *b"PALSFOOT";
+
+// The size of the trailer after the length table.
+const TRAILER_LEN: usize = 16;
+
+// Serialize `data` into a single footer-indexed frame.
+pub fn serialize_footer(data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    let lengths = data.iter().map(|i| i.len());
+    let payload_len = lengths.clone().sum::<usize>();
+    let mut output = Vec::with_capacity(payload_len + PalsConfig::BE.header_len(lengths) + TRAILER_LEN);
+
+    for i in data {
+        output.extend_from_slice(i);
+    }
+
+    PalsConfig::BE.write_header(data, &mut output).map_err(|e| e.shifted(payload_len))?;
+    output.extend_from_slice(&trailer(payload_len as u64));
+
+    Ok(output)
+}
+
+// Deserialize a frame written by `serialize_footer` or `FooterWriter`.
+pub fn deserialize_footer(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    Ok(deserialize_footer_ref(data)?.into_iter().map(|i| i.to_vec()).collect())
+}
+
+// Same as `deserialize_footer`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_footer_ref(data: &[u8]) -> Result<Vec<&[u8]>, PalsError> {
+    if data.len() < TRAILER_LEN {
+        return Err(PalsError::TruncatedPayload { offset: 0, segment: 0, expected: TRAILER_LEN, available: data.len() });
+    }
+
+    let table_end = data.len() - TRAILER_LEN;
+    let table_offset = read_trailer(&data[table_end..], table_end as u64)? as usize;
+    let lengths = read_table(&PalsConfig::BE, &data[table_offset..table_end], table_offset)?;
+
+    let mut output = Vec::with_capacity(lengths.len());
+    let mut start = 0;
+    for len in lengths {
+        output.push(&data[start..(start + len)]);
+        start += len;
+    }
+
+    Ok(output)
+}
+
+// Rewrite a footer-indexed frame in the `serialize_be` layout.
+pub fn footer_to_be(data: &[u8]) -> Result<Vec<u8>, PalsError> {
+    crate::serialize_be(&deserialize_footer_ref(data)?)
+}
+
+// Rewrite a frame written by `serialize_be` as a footer-indexed frame.
+// The frame has to fill all of `data`.
+pub fn be_to_footer(data: &[u8]) -> Result<Vec<u8>, PalsError> {
+    serialize_footer(&decode_frame_exact(&PalsConfig::BE, data)?)
+}
+
+fn trailer(table_offset: u64) -> [u8; TRAILER_LEN] {
+    let mut trailer = [0; TRAILER_LEN];
+    trailer[..8].copy_from_slice(&table_offset.to_be_bytes());
+    trailer[8..].copy_from_slice(&MAGIC);
+    trailer
+}
+
+// Check the trailer, which starts `table_end` bytes into the input, and return
+// the offset of the length table.
+fn read_trailer(trailer: &[u8], table_end: u64) -> Result<u64, PalsError> {
+    if trailer[8..] != MAGIC {
+        return Err(PalsError::BadMagic {
+            offset: table_end as usize + 8,
+            segment: 0,
+            expected: MAGIC.len(),
+            available: MAGIC.len(),
+        });
+    }
+
+    let table_offset = u64::from_be_bytes(trailer[..8].try_into().unwrap());
+    if table_offset > table_end {
+        return Err(PalsError::InvalidValue {
+            offset: table_end as usize,
+            segment: 0,
+            expected: table_end as usize,
+            available: table_offset as usize,
+        });
+    }
+
+    Ok(table_offset)
+}
+
+// Parse the length table found at `table_offset`, which must fill `table` and
+// describe exactly the `table_offset` bytes of payload in front of it.
+// `config` is `PalsConfig::BE`, possibly with limits.
+fn read_table(config: &PalsConfig, table: &[u8], table_offset: usize) -> Result<Vec<usize>, PalsError> {
+    let (lengths, end) = config.read_header(table).map_err(|e| e.shifted(table_offset))?;
+
+    if end != table.len() {
+        return Err(PalsError::TrailingBytes {
+            offset: table_offset + end,
+            segment: lengths.len(),
+            expected: table_offset + end,
+            available: table_offset + table.len(),
+        });
+    }
+
+    // The sum cannot overflow, since `read_header` already checked it.
+    let payload_len = lengths.iter().sum::<usize>();
+    if payload_len != table_offset {
+        return Err(PalsError::InvalidLength {
+            offset: table_offset,
+            segment: lengths.len(),
+            expected: table_offset,
+            available: payload_len,
+        });
+    }
+
+    Ok(lengths)
+}
+
+// Writes a footer-indexed frame to any `Write` sink, one segment at a time.
+// Segment bytes go straight through to the sink, so a segment can be written
+// in as many pieces as needed and is never held in memory; only its length
+// is kept until `finish` writes the length table and trailer.
+//
+// Small writes go straight to the sink, so unbuffered sinks should be wrapped
+// in a `BufWriter` first.
+#[cfg(feature = "std")]
+pub struct FooterWriter<W> {
+    inner: W,
+    lengths: Vec<usize>,
+    // The bytes written to the current segment, if one has been started.
+    current: Option<usize>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> FooterWriter<W> {
+    pub fn new(inner: W) -> Self {
+        FooterWriter { inner, lengths: Vec::new(), current: None }
+    }
+
+    // Write a whole segment, ending the current one first if there is one.
+    pub fn write_segment(&mut self, data: &[u8]) -> io::Result<()> {
+        self.begin_segment();
+        self.write_all(data)?;
+        self.end_segment();
+        Ok(())
+    }
+
+    // End the segment the bytes written through `Write` belong to. The next
+    // write starts a new segment. Does nothing if no segment was started.
+    pub fn end_segment(&mut self) {
+        if let Some(len) = self.current.take() {
+            self.lengths.push(len);
+        }
+    }
+
+    // Start a new, still empty segment, ending the current one first.
+    // Needed to write an empty segment through `Write`.
+    pub fn begin_segment(&mut self) {
+        self.end_segment();
+        self.current = Some(0);
+    }
+
+    // The number of segments written so far, including the current one.
+    pub fn len(&self) -> usize {
+        self.lengths.len() + self.current.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // End the current segment, write the length table and trailer, and return
+    // the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_segment();
+
+        let mut footer = Vec::with_capacity(PalsConfig::BE.header_len(self.lengths.iter().copied()) + TRAILER_LEN);
+        PalsConfig::BE.write_lengths(self.lengths.iter().copied(), &mut footer)?;
+        footer.extend_from_slice(&trailer(self.lengths.iter().map(|i| *i as u64).sum()));
+
+        self.inner.write_all(&footer)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+}
+
+// Appends to the current segment, starting one if needed.
+#[cfg(feature = "std")]
+impl<W: Write> Write for FooterWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        *self.current.get_or_insert(0) += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// How much of the length table `FooterReader` reserves room for up front.
+#[cfg(feature = "std")]
+const READ_CHUNK: usize = 64 * 1024;
+
+// Check the position of a length table read from the trailer of a seekable
+// source, before the table is read. Returns the offset and size of the table
+// as `usize`, after checking that both fit and that the table has no more
+// entries than `limits` allow.
+#[cfg(feature = "std")]
+fn table_bounds(table_offset: u64, table_end: u64, limits: &DecodeLimits) -> Result<(usize, usize), PalsError> {
+    let (Ok(start), Ok(len)) = (usize::try_from(table_offset), usize::try_from(table_end - table_offset)) else {
+        return Err(PalsError::LengthOverflow {
+            offset: usize::try_from(table_end).unwrap_or(usize::MAX),
+            segment: 0,
+            expected: usize::MAX,
+            available: usize::try_from(table_end - table_offset).unwrap_or(usize::MAX),
+        });
+    };
+
+    // Every entry, the terminator included, takes 8 bytes.
+    let segments = (len / 8).saturating_sub(1);
+    if segments > limits.max_segments {
+        return Err(PalsError::TooManySegments {
+            offset: start + 8 * limits.max_segments,
+            segment: limits.max_segments,
+            expected: limits.max_segments,
+            available: segments,
+        });
+    }
+
+    Ok((start, len))
+}
+
+// Reads the segments of a footer-indexed frame from a seekable source, such
+// as a file. Opening it reads only the trailer and the length table; segments
+// are read on demand, whole or through a reader of their own.
+#[cfg(feature = "std")]
+pub struct FooterReader<R> {
+    inner: R,
+    lengths: Vec<usize>,
+    // The offset of every segment in the source.
+    offsets: Vec<u64>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> FooterReader<R> {
+    // Read the trailer and length table of the frame that ends where `inner`
+    // ends. Malformed frames fail with `io::ErrorKind::InvalidData`; the
+    // underlying `PalsError` can be recovered from the `io::Error`.
+    pub fn new(inner: R) -> io::Result<Self> {
+        FooterReader::with_limits(inner, DecodeLimits::UNLIMITED)
+    }
+
+    // Same as `new`, but rejects frames that exceed `limits`. The size of the
+    // length table is checked against `limits.max_segments` before any of it
+    // is read.
+    pub fn with_limits(mut inner: R, limits: DecodeLimits) -> io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        if len < TRAILER_LEN as u64 {
+            let err = PalsError::TruncatedPayload {
+                offset: 0,
+                segment: 0,
+                expected: TRAILER_LEN,
+                available: len as usize,
+            };
+            return Err(err.into());
+        }
+
+        let table_end = len - TRAILER_LEN as u64;
+        let mut trailer = [0; TRAILER_LEN];
+        inner.seek(SeekFrom::Start(table_end))?;
+        inner.read_exact(&mut trailer)?;
+        let table_offset = read_trailer(&trailer, table_end)?;
+
+        // The trailer is not trusted yet, so size up the table it points at
+        // before reading it, and let the buffer grow only with bytes actually read.
+        let (table_start, table_len) = table_bounds(table_offset, table_end, &limits)?;
+        let mut table = Vec::with_capacity(table_len.min(READ_CHUNK));
+        inner.seek(SeekFrom::Start(table_offset))?;
+        (&mut inner).take(table_len as u64).read_to_end(&mut table)?;
+        if table.len() < table_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let lengths = read_table(&PalsConfig::BE.with_limits(limits), &table, table_start)?;
+
+        let mut offsets = Vec::with_capacity(lengths.len());
+        let mut offset = 0;
+        for len in &lengths {
+            offsets.push(offset);
+            offset += *len as u64;
+        }
+
+        Ok(FooterReader { inner, lengths, offsets })
+    }
+
+    // The number of segments in the frame.
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    // The length of the segment at `index`, or `None` if there are not that many segments.
+    pub fn segment_len(&self, index: usize) -> Option<usize> {
+        self.lengths.get(index).copied()
+    }
+
+    // Read the whole segment at `index` into a new vector.
+    pub fn read_segment(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(self.lengths.get(index).copied().unwrap_or(0));
+        self.segment_reader(index)?.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    // A reader over the segment at `index`, for segments too large to read whole.
+    pub fn segment_reader(&mut self, index: usize) -> io::Result<io::Take<&mut R>> {
+        let (Some(offset), Some(len)) = (self.offsets.get(index), self.lengths.get(index)) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "segment index out of range"));
+        };
+
+        self.inner.seek(SeekFrom::Start(*offset))?;
+        Ok((&mut self.inner).take(*len as u64))
+    }
+
+    // Unwrap this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_be, serialize_be};
+
+    #[test]
+    fn test_serialize_deserialize_footer() {
+        let data: [&[u8]; 3] = [b"abc", b"", b"defg"];
+
+        let serialized = serialize_footer(&data).unwrap();
+
+        assert_eq!(&serialized[..7], b"abcdefg");
+        assert_eq!(&serialized[7..39], &serialize_be(&data).unwrap()[..32]);
+        assert_eq!(&serialized[39..], b"\0\0\0\0\0\0\0\x07PALSFOOT");
+        assert_eq!(deserialize_footer(&serialized).unwrap(), data);
+        assert_eq!(deserialize_footer(&serialize_footer(&[]).unwrap()).unwrap(), Vec::<Vec<u8>>::new());
+    }
+
+    #[test]
+    fn test_convert_footer_and_be() {
+        let data: [&[u8]; 2] = [b"hello", b"world"];
+        let be = serialize_be(&data).unwrap();
+        let footer = serialize_footer(&data).unwrap();
+
+        assert_eq!(be_to_footer(&be).unwrap(), footer);
+        assert_eq!(footer_to_be(&footer).unwrap(), be);
+        assert_eq!(deserialize_be(&footer_to_be(&be_to_footer(&be).unwrap()).unwrap()).unwrap(), data);
+        assert!(matches!(be_to_footer(&[&be[..], b"x"].concat()), Err(PalsError::TrailingBytes { .. })));
+    }
+
+    #[test]
+    fn test_deserialize_footer_malformed() {
+        let serialized = serialize_footer(&[b"abc", b"de"]).unwrap();
+        let len = serialized.len();
+
+        assert!(matches!(
+            deserialize_footer(&serialized[..10]),
+            Err(PalsError::TruncatedPayload { expected: 16, available: 10, .. })
+        ));
+
+        let mut bad = serialized.clone();
+        bad[len - 1] ^= 1;
+        assert!(matches!(deserialize_footer(&bad), Err(PalsError::BadMagic { .. })));
+
+        let mut bad = serialized.clone();
+        bad[len - 9] = 0xff;
+        assert!(matches!(deserialize_footer(&bad), Err(PalsError::InvalidValue { available: 0xff, .. })));
+
+        let mut bad = serialize_footer(&[b"ab"]).unwrap();
+        bad.insert(18, 0);
+        assert!(matches!(deserialize_footer(&bad), Err(PalsError::TrailingBytes { offset: 18, .. })));
+
+        let mut bad = serialized.clone();
+        bad[12] = 3;
+        assert!(matches!(deserialize_footer(&bad), Err(PalsError::InvalidLength { expected: 5, available: 4, .. })));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_write_read_footer_stream() {
+        let mut writer = FooterWriter::new(Vec::new());
+        writer.write_all(b"chunk one, ").unwrap();
+        writer.write_all(b"chunk two").unwrap();
+        writer.end_segment();
+        writer.begin_segment();
+        writer.write_segment(b"last").unwrap();
+        assert_eq!(writer.len(), 3);
+
+        let serialized = writer.finish().unwrap();
+        assert_eq!(serialized, serialize_footer(&[b"chunk one, chunk two", b"", b"last"]).unwrap());
+
+        let mut reader = FooterReader::new(io::Cursor::new(serialized)).unwrap();
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.segment_len(0), Some(20));
+        assert_eq!(reader.read_segment(2).unwrap(), b"last");
+        assert_eq!(reader.read_segment(1).unwrap(), b"");
+
+        let mut start = [0; 5];
+        reader.segment_reader(0).unwrap().read_exact(&mut start).unwrap();
+        assert_eq!(&start, b"chunk");
+        assert_eq!(reader.segment_reader(3).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_footer_malformed() {
+        let err = FooterReader::new(io::Cursor::new(b"short")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut serialized = serialize_footer(&[b"abc"]).unwrap();
+        let len = serialized.len();
+        serialized[len - 2] = b'X';
+
+        let err = FooterReader::new(io::Cursor::new(serialized)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(err.get_ref().unwrap().downcast_ref::<PalsError>(), Some(PalsError::BadMagic { .. })));
+    }
+
+    // A source of `len` bytes of which only the trailer can be read, pointing
+    // at a length table that starts at offset 0.
+    #[cfg(feature = "std")]
+    struct Forged {
+        len: u64,
+        pos: u64,
+    }
+
+    #[cfg(feature = "std")]
+    impl Read for Forged {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let trailer_start = self.len - TRAILER_LEN as u64;
+            if self.pos < trailer_start {
+                return Ok(0);
+            }
+
+            let trailer = trailer(0);
+            let rest = &trailer[(self.pos - trailer_start) as usize..];
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            self.pos += n as u64;
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl Seek for Forged {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.pos = match pos {
+                SeekFrom::Start(n) => n,
+                SeekFrom::End(n) => self.len.checked_add_signed(n).unwrap(),
+                SeekFrom::Current(n) => self.pos.checked_add_signed(n).unwrap(),
+            };
+            Ok(self.pos)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_footer_forged_table_offset() {
+        // The trailer claims a table of about 2^60 bytes; nothing that large is allocated.
+        let limits = DecodeLimits { max_segments: 1000, ..DecodeLimits::UNLIMITED };
+        let err = FooterReader::with_limits(Forged { len: 1 << 60, pos: 0 }, limits).err().unwrap();
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<PalsError>(),
+            Some(PalsError::TooManySegments { offset: 8000, expected: 1000, .. })
+        ));
+
+        let err = FooterReader::new(Forged { len: 1 << 60, pos: 0 }).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // A real file whose trailer points into the payload.
+        let mut serialized = serialize_footer(&[&[7; 1000]]).unwrap();
+        let len = serialized.len();
+        serialized[(len - TRAILER_LEN)..(len - 8)].copy_from_slice(&0u64.to_be_bytes());
+
+        let limits = DecodeLimits { max_segments: 4, ..DecodeLimits::UNLIMITED };
+        let err = FooterReader::with_limits(io::Cursor::new(&serialized), limits).err().unwrap();
+        assert!(matches!(err.get_ref().unwrap().downcast_ref::<PalsError>(), Some(PalsError::TooManySegments { .. })));
+        assert!(FooterReader::new(io::Cursor::new(&serialized)).is_err());
+    }
+}
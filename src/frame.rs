@@ -0,0 +1,147 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{decode_frame, PalsConfig, PalsError};
+
+// A validated view of a frame that looks up segments without copying them.
+// Parsing walks the length table once; after that `get` and `payload_range`
+// are constant time, so picking a single segment out of a large frame is cheap.
+#[derive(Debug, Clone)]
+pub struct Frame<'a> {
+    data: &'a [u8],
+    segments: Vec<&'a [u8]>,
+    end: usize,
+}
+
+impl<'a> Frame<'a> {
+    // Parse a frame laid out as described by `config` at the start of `data`.
+    // Bytes after the end of the frame are ignored.
+    pub fn parse_with(config: &PalsConfig, data: &'a [u8]) -> Result<Self, PalsError> {
+        let (segments, end) = decode_frame(config, data, 0)?;
+        Ok(Frame { data, segments, end })
+    }
+
+    // Parse a frame written by `serialize_be`.
+    pub fn parse_be(data: &'a [u8]) -> Result<Self, PalsError> {
+        Frame::parse_with(&PalsConfig::BE, data)
+    }
+
+    // Parse a frame written by `serialize_le`.
+    pub fn parse_le(data: &'a [u8]) -> Result<Self, PalsError> {
+        Frame::parse_with(&PalsConfig::LE, data)
+    }
+
+    // The number of segments in the frame.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    // The segment at `index`, or `None` if there are not that many segments.
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        self.segments.get(index).copied()
+    }
+
+    // Iterate over the segments in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &'a [u8]> + '_ {
+        self.segments.iter().copied()
+    }
+
+    // The position of the segment at `index` in the parsed input.
+    pub fn payload_range(&self, index: usize) -> Option<Range<usize>> {
+        let segment = self.segments.get(index)?;
+        let start = segment.as_ptr() as usize - self.data.as_ptr() as usize;
+        Some(start..(start + segment.len()))
+    }
+
+    // The number of input bytes the frame takes up, including its checksums.
+    pub fn frame_len(&self) -> usize {
+        self.end
+    }
+}
+
+impl<'b, 'a> IntoIterator for &'b Frame<'a> {
+    type Item = &'a [u8];
+    type IntoIter = core::iter::Copied<core::slice::Iter<'b, &'a [u8]>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_be, serialize_le, serialize_with, Checksum};
+
+    #[test]
+    fn test_random_access() {
+        let data: Vec<Vec<u8>> = (0..40u8).map(|i| vec![i; i as usize]).collect();
+        let refs: Vec<&[u8]> = data.iter().map(|i| i.as_slice()).collect();
+        let serialized = serialize_be(&refs).unwrap();
+
+        let frame = Frame::parse_be(&serialized).unwrap();
+
+        assert_eq!(frame.len(), 40);
+        assert_eq!(frame.get(7), Some(&[7u8; 7][..]));
+        assert_eq!(frame.get(40), None);
+        assert_eq!(&serialized[frame.payload_range(7).unwrap()], &[7; 7]);
+        assert_eq!(frame.payload_range(40), None);
+        assert_eq!(frame.iter().collect::<Vec<_>>(), refs);
+        assert_eq!(frame.frame_len(), serialized.len());
+    }
+
+    #[test]
+    fn test_parse_le_and_trailing_bytes() {
+        let mut serialized = serialize_le(&[b"ab", b"", b"c"]).unwrap();
+        let len = serialized.len();
+        serialized.extend_from_slice(b"next frame");
+
+        let frame = Frame::parse_le(&serialized).unwrap();
+
+        assert_eq!(frame.iter().collect::<Vec<_>>(), [&b"ab"[..], b"", b"c"]);
+        assert_eq!(frame.payload_range(0), Some(4..6));
+        assert_eq!(frame.frame_len(), len);
+    }
+
+    #[test]
+    fn test_parse_verifies_checksums() {
+        let config = PalsConfig::BE.with_checksum(Checksum::Segments);
+        let mut serialized = serialize_with(&config, &[b"abc"]).unwrap();
+        serialized[16] ^= 1;
+
+        assert!(matches!(Frame::parse_with(&config, &serialized), Err(PalsError::SegmentChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_hostile_input_does_not_panic() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut input = Vec::new();
+
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Mostly small bytes, so that length tables are plausible.
+            input.push(if state % 3 == 0 { (state >> 8) as u8 } else { (state >> 8) as u8 % 4 });
+
+            for start in [0, input.len().saturating_sub(24)] {
+                let _ = Frame::parse_be(&input[start..]);
+                let _ = Frame::parse_le(&input[start..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_frame() {
+        let frame = Frame::parse_be(&[0; 8]).unwrap();
+
+        assert!(frame.is_empty());
+        assert_eq!(frame.get(0), None);
+        assert_eq!(frame.iter().count(), 0);
+        assert_eq!(frame.frame_len(), 8);
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use crate::{Frame, PalsConfig, PalsError};
+#[cfg(feature = "std")]
+use crate::PalsReader;
+
+// Walks a buffer of frames written back to back, such as an append-only log,
+// and yields each frame along with the offset it starts at.
+//
+// Iteration ends cleanly at the end of the buffer. If the last frame was cut
+// short, `PalsError::TornTail` is yielded instead, pointing at the start of
+// that frame, so recovery code can cut the buffer back to `valid_len`.
+// Any other error is yielded once and also ends the iteration.
+pub struct FrameIter<'a> {
+    data: &'a [u8],
+    config: PalsConfig,
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> FrameIter<'a> {
+    // Iterate over frames laid out as described by `config`.
+    pub fn with_config(data: &'a [u8], config: PalsConfig) -> Self {
+        FrameIter { data, config, offset: 0, done: false }
+    }
+
+    // Iterate over frames written by `serialize_le`.
+    pub fn le(data: &'a [u8]) -> Self {
+        Self::with_config(data, PalsConfig::LE)
+    }
+
+    // Iterate over frames written by `serialize_be`.
+    pub fn be(data: &'a [u8]) -> Self {
+        Self::with_config(data, PalsConfig::BE)
+    }
+
+    // Iterate over frames written by `serialize_varint`.
+    pub fn varint(data: &'a [u8]) -> Self {
+        Self::with_config(data, PalsConfig::VARINT)
+    }
+
+    // The number of bytes taken up by the frames yielded so far.
+    pub fn valid_len(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for FrameIter<'a> {
+    // The offset of the frame in the buffer, and the frame. The segment
+    // ranges of the frame are relative to its own start.
+    type Item = Result<(usize, Frame<'a>), PalsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset == self.data.len() {
+            return None;
+        }
+
+        let start = self.offset;
+        match Frame::parse_with(&self.config, &self.data[start..]) {
+            Ok(frame) => {
+                self.offset += frame.frame_len();
+                Some(Ok((start, frame)))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(torn_tail(e, start)))
+            }
+        }
+    }
+}
+
+// Reads frames written back to back from any `Read` source, such as a log
+// file, and yields each frame along with the offset it starts at. Errors are
+// reported the same way as by `FrameIter`, wrapped in an `io::Error`.
+#[cfg(feature = "std")]
+pub struct StreamFrameIter<R> {
+    reader: PalsReader<Counting<R>>,
+    valid_len: u64,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> StreamFrameIter<R> {
+    // Iterate over frames laid out as described by `config`.
+    pub fn with_config(inner: R, config: PalsConfig) -> Self {
+        let reader = PalsReader::with_config(Counting { inner, count: 0 }, config);
+        StreamFrameIter { reader, valid_len: 0, done: false }
+    }
+
+    // Iterate over frames written by `serialize_le`.
+    pub fn le(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::LE)
+    }
+
+    // Iterate over frames written by `serialize_be`.
+    pub fn be(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::BE)
+    }
+
+    // Iterate over frames written by `serialize_varint`.
+    pub fn varint(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::VARINT)
+    }
+
+    // The number of bytes taken up by the frames yielded so far.
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    // Unwrap this iterator, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner().inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for StreamFrameIter<R> {
+    type Item = io::Result<(u64, Vec<Vec<u8>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let start = self.valid_len();
+        match self.reader.read_frame() {
+            Ok(Some(frame)) => {
+                self.valid_len = self.reader.get_ref().count;
+                Some(Ok((start, frame)))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+
+                // Report errors in the frame against the position in the stream.
+                let err = match e.get_ref().and_then(|i| i.downcast_ref::<PalsError>()) {
+                    Some(inner) => torn_tail(inner.clone(), start as usize).into(),
+                    None => e,
+                };
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// Turn an error that cut the frame starting at `start` short into a
+// `PalsError::TornTail`, and move any other error to its place in the input.
+fn torn_tail(err: PalsError, start: usize) -> PalsError {
+    if !err.is_incomplete() {
+        return err.shifted(start);
+    }
+
+    PalsError::TornTail { offset: start, segment: err.segment(), expected: err.expected(), available: err.available() }
+}
+
+// Counts the bytes read from the inner reader, so frame offsets are known.
+#[cfg(feature = "std")]
+struct Counting<R> {
+    inner: R,
+    count: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_be, serialize_with, Checksum};
+
+    fn log() -> Vec<u8> {
+        let mut log = Vec::new();
+        for i in 0..5u8 {
+            log.extend(serialize_be(&[&[i], &vec![i; i as usize]]).unwrap());
+        }
+        log
+    }
+
+    #[test]
+    fn test_iterate_buffer() {
+        let log = log();
+
+        let frames: Vec<_> = FrameIter::be(&log).map(Result::unwrap).collect();
+
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].0, 0);
+        assert_eq!(frames[1].0, 25);
+        assert_eq!(frames[3].1.get(1), Some(&[3u8; 3][..]));
+        assert_eq!(FrameIter::be(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_iterate_buffer_torn_tail() {
+        let log = log();
+        let last = 25 + 26 + 27 + 28;
+
+        for cut in (last + 1)..log.len() {
+            let mut iter = FrameIter::be(&log[..cut]);
+
+            assert_eq!(iter.by_ref().take(4).filter(Result::is_ok).count(), 4);
+            let err = iter.next().unwrap().unwrap_err();
+            assert!(matches!(err, PalsError::TornTail { offset, .. } if offset == last));
+            assert!(err.is_incomplete());
+            assert!(iter.next().is_none());
+            assert_eq!(iter.valid_len(), last);
+        }
+    }
+
+    #[test]
+    fn test_iterate_buffer_corrupt_frame() {
+        let config = PalsConfig::BE.with_checksum(Checksum::Frame);
+        let mut log = serialize_with(&config, &[b"ok"]).unwrap();
+        let second = log.len();
+        log.extend(serialize_with(&config, &[b"bad"]).unwrap());
+        log[second + 16] ^= 1;
+
+        let mut iter = FrameIter::with_config(&log, config);
+
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.next(), Some(Err(PalsError::FrameChecksumMismatch { offset, .. })) if offset > second));
+        assert!(iter.next().is_none());
+
+        #[cfg(feature = "std")]
+        {
+            let mut iter = StreamFrameIter::with_config(&log[..], config);
+
+            assert!(iter.next().unwrap().is_ok());
+            let err = iter.next().unwrap().unwrap_err();
+            let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+            assert_eq!(inner.offset(), log.len() - 4);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_iterate_stream() {
+        let log = log();
+
+        let frames: Vec<_> = StreamFrameIter::be(&log[..]).map(Result::unwrap).collect();
+        let offsets: Vec<_> = frames.iter().map(|i| i.0).collect();
+
+        assert_eq!(offsets, [0, 25, 51, 78, 106]);
+        assert_eq!(frames[4].1, [vec![4], vec![4; 4]]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_iterate_stream_torn_tail() {
+        let log = log();
+
+        let mut iter = StreamFrameIter::be(&log[..(log.len() - 2)]);
+
+        assert_eq!(iter.by_ref().take(4).filter(Result::is_ok).count(), 4);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+        assert!(matches!(inner, PalsError::TornTail { offset: 106, .. }));
+        assert!(iter.next().is_none());
+        assert_eq!(iter.valid_len(), 106);
+    }
+
+    #[test]
+    fn test_iterate_empty_frames() {
+        let log = [0, 2, 0, b'a', 0];
+
+        let frames: Vec<_> = FrameIter::le(&log).map(|i| i.unwrap()).map(|(offset, i)| (offset, i.len())).collect();
+        assert_eq!(frames, [(0, 0), (1, 1), (4, 0)]);
+
+        #[cfg(feature = "std")]
+        {
+            let frames: Vec<_> = StreamFrameIter::le(&log[..]).map(Result::unwrap).collect();
+            assert_eq!(frames, [(0, vec![]), (1, vec![b"a".to_vec()]), (4, vec![])]);
+        }
+    }
+}
@@ -13,129 +13,499 @@
     The 0 is preserved to be a separator between the lengths and the data. This allows an unlimited
     number of data segments to be serialized, with each segment having a
     maximum length of 2^62 bytes (4 exabytes).
+
+    The width and byte order of the lengths can be chosen with a `PalsConfig`.
+    `serialize_be` uses big-endian u64 lengths as described above, while
+    `serialize_le` uses a single byte per length and `serialize_varint` a
+    LEB128 varint. The terminator is always as wide as one length entry.
+
+    With the `derive` feature, `#[derive(PalsEncode, PalsDecode)]` maps the
+    fields of a struct onto the segments of a `serialize_be` frame without
+    going through serde.
+
+    The crate is `no_std` with `alloc` when the default `std` feature is
+    turned off. That leaves out `PalsReader`, `PalsWriter`, `StreamFrameIter`
+    and everything else built on `std::io`. `encoded_len_*` and
+    `serialize_*_into` do not allocate at all, so frames can be written into
+    a fixed buffer; `Frame` needs one vector for its table of segments.
 */
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+// Lets the code generated by the derive macros name this crate from inside it.
+extern crate self as palserializer;
+
+mod builder;
+mod checksum;
+#[cfg(feature = "tokio")]
+mod codec;
+mod config;
+mod decoder;
+mod encode;
+mod error;
+mod footer;
+mod frame;
+mod iter;
+mod output;
+mod owned;
+mod preamble;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod varint;
+#[cfg(feature = "std")]
+mod vectored;
+#[cfg(feature = "std")]
+mod writer;
+
+pub use builder::FrameBuilder;
+pub use checksum::Checksum;
+#[cfg(feature = "tokio")]
+pub use codec::PalsCodec;
+pub use config::{ByteOrder, DecodeLimits, PalsConfig, Width};
+pub use decoder::{Event, Feed, PalsDecoder};
+pub use encode::{decode, encode, Decode, Encode, FrameDecoder, FrameEncoder};
+pub use error::PalsError;
+pub use footer::{be_to_footer, deserialize_footer, deserialize_footer_ref, footer_to_be, serialize_footer};
+#[cfg(feature = "std")]
+pub use footer::{FooterReader, FooterWriter};
+pub use frame::Frame;
+pub use iter::FrameIter;
+#[cfg(feature = "std")]
+pub use iter::StreamFrameIter;
+pub use owned::OwnedFrame;
+#[cfg(feature = "derive")]
+pub use palserializer_derive::{PalsDecode, PalsEncode};
+pub use preamble::Preamble;
+#[cfg(feature = "std")]
+pub use reader::PalsReader;
+#[cfg(feature = "std")]
+pub use vectored::{write_all_vectored, VectoredFrame};
+#[cfg(feature = "std")]
+pub use writer::PalsWriter;
+
+// Lets the code generated by the derive macros name `Vec` without `std`, from
+// crates that do not declare `alloc` themselves.
+#[doc(hidden)]
+pub use alloc::vec::Vec as __Vec;
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use checksum::{crc32c, verify_trailer, write_trailer, Crc32c};
+use output::{CountOutput, Output, SliceOutput};
+
+// Serialize `data` into a single frame laid out as described by `config`.
+// All other serializers are shorthands for this function.
+pub fn serialize_with(config: &PalsConfig, data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    encode_frame(config, data, false)
+}
 
-use std::convert::TryInto;
+// Deserialize a frame laid out as described by `config`.
+// All other deserializers are shorthands for this function.
+pub fn deserialize_with(config: &PalsConfig, data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    Ok(deserialize_with_ref(config, data)?.into_iter().map(|i| i.to_vec()).collect())
+}
 
-pub fn serialize_le(data: &[&[u8]]) -> Result<Vec<u8>, String> {
-    if data.is_empty() {
-        return Err("Input data is empty.".to_string());
-    }
+// Same as `deserialize_with`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_with_ref<'a>(config: &PalsConfig, data: &'a [u8]) -> Result<Vec<&'a [u8]>, PalsError> {
+    Ok(decode_frame(config, data, 0)?.0)
+}
 
-    let mut output = Vec::new();
+// Deserialize the frame at the start of `data` and return its segments along
+// with the number of bytes it took up. Bytes after the frame are left alone,
+// so a buffer of concatenated frames can be walked by slicing off `consumed`
+// bytes at a time. The segments borrow from `data` like the `_ref` variants.
+pub fn deserialize_with_partial<'a>(config: &PalsConfig, data: &'a [u8]) -> Result<(Vec<&'a [u8]>, usize), PalsError> {
+    decode_frame(config, data, 0)
+}
 
-    for i in data {
-        if i.len() > (u8::MAX as usize) - 1 {
-            return Err("Input data contains a slice that is too large to be serialized.".to_string());
-        }
-        output.extend_from_slice(&((1 + i.len()) as u8).to_le_bytes());
-    }
+// Same as `deserialize_with`, but fails with `PalsError::TrailingBytes` unless
+// the frame ends exactly where `data` does.
+pub fn deserialize_with_strict(config: &PalsConfig, data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    Ok(decode_frame_exact(config, data)?.into_iter().map(|i| i.to_vec()).collect())
+}
 
-    output.push(0);
+// The exact number of bytes `serialize_with` produces for `data`, including
+// the checksum trailer. Useful to size a buffer for `serialize_with_into`.
+pub fn encoded_len_with(config: &PalsConfig, data: &[&[u8]]) -> usize {
+    config.header_len(data.iter().map(|i| i.len()))
+        + data.iter().map(|i| i.len()).sum::<usize>()
+        + config.checksum.trailer_len(data.len())
+}
 
-    for i in data {
-        output.extend_from_slice(i);
+// Same as `serialize_with`, but writes the frame to the start of `output`
+// instead of allocating, and returns its length. Fails with
+// `PalsError::BufferTooSmall`, whose `expected` field holds the required size,
+// if `output` cannot hold the whole frame. A frame that cannot be encoded at
+// all is reported as such first. Bytes after the frame are untouched.
+pub fn serialize_with_into(config: &PalsConfig, data: &[&[u8]], output: &mut [u8]) -> Result<usize, PalsError> {
+    check_head(config, data.iter().map(|i| i.len()))?;
+
+    let len = encoded_len_with(config, data);
+    if output.len() < len {
+        return Err(PalsError::BufferTooSmall { offset: 0, segment: 0, expected: len, available: output.len() });
     }
 
-    Ok(output)
+    write_frame(config, data, false, &mut SliceOutput::new(output))?;
+    Ok(len)
+}
+
+// Same as `serialize_with`, but appends the frame to `output` and returns its
+// length. `output` grows at most once, and not at all if it already has room.
+// On failure it is left as it was.
+pub fn serialize_with_append(config: &PalsConfig, data: &[&[u8]], output: &mut Vec<u8>) -> Result<usize, PalsError> {
+    check_head(config, data.iter().map(|i| i.len()))?;
+
+    let len = encoded_len_with(config, data);
+    output.reserve(len);
+
+    write_frame(config, data, false, output)?;
+    Ok(len)
+}
+
+// Serialize like `serialize_with`, but start the frame with a `Preamble`
+// recording the config, so it can later be read back by `deserialize_auto`.
+pub fn serialize_with_preamble(config: &PalsConfig, data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    encode_frame(config, data, true)
+}
+
+// Deserialize a frame written by `serialize_with_preamble`, using whatever
+// config its preamble names.
+pub fn deserialize_auto(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    Ok(deserialize_auto_ref(data)?.into_iter().map(|i| i.to_vec()).collect())
+}
+
+// Same as `deserialize_auto`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_auto_ref(data: &[u8]) -> Result<Vec<&[u8]>, PalsError> {
+    deserialize_auto_ref_with_limits(&DecodeLimits::UNLIMITED, data)
+}
+
+// Same as `deserialize_auto_ref`, but rejects frames that exceed `limits`.
+// The preamble only describes the layout, so the limits have to come from the caller.
+pub fn deserialize_auto_ref_with_limits<'a>(limits: &DecodeLimits, data: &'a [u8]) -> Result<Vec<&'a [u8]>, PalsError> {
+    let config = Preamble::read(data)?.config.with_limits(*limits);
+    Ok(decode_frame(&config, data, Preamble::LEN)?.0)
+}
+
+// Same as `deserialize_auto_ref`, but also returns the number of bytes consumed,
+// preamble included, see `deserialize_with_partial`.
+pub fn deserialize_auto_partial(data: &[u8]) -> Result<(Vec<&[u8]>, usize), PalsError> {
+    let config = Preamble::read(data)?.config;
+    decode_frame(&config, data, Preamble::LEN)
+}
+
+// Serialize with one byte per length, see `PalsConfig::LE`.
+// Segments can be at most 254 bytes long.
+pub fn serialize_le(data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    serialize_with(&PalsConfig::LE, data)
 }
 
 // Serialize a vector of byte vectors into a single byte slice.
 // Each byte vector represents a slice of the output data.
-pub fn serialize_be(data: &[&[u8]]) -> Result<Vec<u8>, String> {
-    if data.is_empty() {
-        return Err("Input data is empty.".to_string());
-    }
+pub fn serialize_be(data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    serialize_with(&PalsConfig::BE, data)
+}
 
-    let mut output = Vec::new(); // Initialize an empty vector to hold the output data.
+// Serialize like `serialize_be`, but store every length as an unsigned LEB128
+// varint, so segments shorter than 127 bytes only cost one byte of header.
+pub fn serialize_varint(data: &[&[u8]]) -> Result<Vec<u8>, PalsError> {
+    serialize_with(&PalsConfig::VARINT, data)
+}
 
-    // Loop through the input data and add the length of each slice to the output vector.
-    for i in data {
-        output.extend_from_slice(&((1 + i.len()) as u64).to_be_bytes()); // Add the length to the output vector.
-    }
+// The exact number of bytes `serialize_le` produces for `data`.
+pub fn encoded_len_le(data: &[&[u8]]) -> usize {
+    encoded_len_with(&PalsConfig::LE, data)
+}
 
-    output.extend_from_slice(&[0; 8]); // Add a zero-length slice to the output vector.
+// The exact number of bytes `serialize_be` produces for `data`.
+pub fn encoded_len_be(data: &[&[u8]]) -> usize {
+    encoded_len_with(&PalsConfig::BE, data)
+}
 
-    // Loop through the input data and add each slice to the output vector.
-    for i in data {
-        output.extend_from_slice(i); // Add the slice to the output vector.
-    }
+// The exact number of bytes `serialize_varint` produces for `data`.
+pub fn encoded_len_varint(data: &[&[u8]]) -> usize {
+    encoded_len_with(&PalsConfig::VARINT, data)
+}
+
+// Same as `serialize_le`, but writes into `output`, see `serialize_with_into`.
+pub fn serialize_le_into(data: &[&[u8]], output: &mut [u8]) -> Result<usize, PalsError> {
+    serialize_with_into(&PalsConfig::LE, data, output)
+}
+
+// Same as `serialize_be`, but writes into `output`, see `serialize_with_into`.
+pub fn serialize_be_into(data: &[&[u8]], output: &mut [u8]) -> Result<usize, PalsError> {
+    serialize_with_into(&PalsConfig::BE, data, output)
+}
+
+// Same as `serialize_varint`, but writes into `output`, see `serialize_with_into`.
+pub fn serialize_varint_into(data: &[&[u8]], output: &mut [u8]) -> Result<usize, PalsError> {
+    serialize_with_into(&PalsConfig::VARINT, data, output)
+}
+
+// Same as `serialize_le`, but appends to `output`, see `serialize_with_append`.
+pub fn serialize_le_append(data: &[&[u8]], output: &mut Vec<u8>) -> Result<usize, PalsError> {
+    serialize_with_append(&PalsConfig::LE, data, output)
+}
+
+// Same as `serialize_be`, but appends to `output`, see `serialize_with_append`.
+pub fn serialize_be_append(data: &[&[u8]], output: &mut Vec<u8>) -> Result<usize, PalsError> {
+    serialize_with_append(&PalsConfig::BE, data, output)
+}
+
+// Same as `serialize_varint`, but appends to `output`, see `serialize_with_append`.
+pub fn serialize_varint_append(data: &[&[u8]], output: &mut Vec<u8>) -> Result<usize, PalsError> {
+    serialize_with_append(&PalsConfig::VARINT, data, output)
+}
+
+pub fn deserialize_le(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with(&PalsConfig::LE, data)
+}
+
+// Deserialize a byte slice into a vector of byte vectors.
+// Each byte vector represents a slice of the input data.
+pub fn deserialize_be(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with(&PalsConfig::BE, data)
+}
+
+// Deserialize a byte slice written by `serialize_varint`.
+// Lengths that are not in their shortest encoding are rejected.
+pub fn deserialize_varint(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with(&PalsConfig::VARINT, data)
+}
+
+// Same as `deserialize_le`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_le_ref(data: &[u8]) -> Result<Vec<&[u8]>, PalsError> {
+    deserialize_with_ref(&PalsConfig::LE, data)
+}
+
+// Same as `deserialize_be`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_be_ref(data: &[u8]) -> Result<Vec<&[u8]>, PalsError> {
+    deserialize_with_ref(&PalsConfig::BE, data)
+}
+
+// Same as `deserialize_varint`, but the returned segments borrow from `data`
+// instead of being copied into new vectors.
+pub fn deserialize_varint_ref(data: &[u8]) -> Result<Vec<&[u8]>, PalsError> {
+    deserialize_with_ref(&PalsConfig::VARINT, data)
+}
+
+// Same as `deserialize_le`, but also returns the number of bytes consumed,
+// see `deserialize_with_partial`.
+pub fn deserialize_le_partial(data: &[u8]) -> Result<(Vec<&[u8]>, usize), PalsError> {
+    deserialize_with_partial(&PalsConfig::LE, data)
+}
+
+// Same as `deserialize_be`, but also returns the number of bytes consumed,
+// see `deserialize_with_partial`.
+pub fn deserialize_be_partial(data: &[u8]) -> Result<(Vec<&[u8]>, usize), PalsError> {
+    deserialize_with_partial(&PalsConfig::BE, data)
+}
+
+// Same as `deserialize_varint`, but also returns the number of bytes consumed,
+// see `deserialize_with_partial`.
+pub fn deserialize_varint_partial(data: &[u8]) -> Result<(Vec<&[u8]>, usize), PalsError> {
+    deserialize_with_partial(&PalsConfig::VARINT, data)
+}
+
+// Same as `deserialize_le`, but rejects bytes after the end of the frame.
+pub fn deserialize_le_strict(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with_strict(&PalsConfig::LE, data)
+}
+
+// Same as `deserialize_be`, but rejects bytes after the end of the frame.
+pub fn deserialize_be_strict(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with_strict(&PalsConfig::BE, data)
+}
+
+// Same as `deserialize_varint`, but rejects bytes after the end of the frame.
+pub fn deserialize_varint_strict(data: &[u8]) -> Result<Vec<Vec<u8>>, PalsError> {
+    deserialize_with_strict(&PalsConfig::VARINT, data)
+}
+
+// Build a whole frame: the optional preamble, the length table, the payload
+// and the checksum trailer.
+fn encode_frame(config: &PalsConfig, data: &[&[u8]], preamble: bool) -> Result<Vec<u8>, PalsError> {
+    // Initialize a vector large enough to hold the whole output data.
+    let preamble_len = if preamble { Preamble::LEN } else { 0 };
+    let mut output = Vec::with_capacity(preamble_len + encoded_len_with(config, data));
+
+    write_frame(config, data, preamble, &mut output)?;
 
     Ok(output) // Return the output vector.
 }
 
-pub fn deserialize_le(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
-    let mut output = Vec::new();
-    let mut lengths = Vec::new();
+// Append a whole frame to `output`, which must have room for it.
+// Error offsets are relative to the start of the frame, and on failure
+// `output` is cut back to where it was.
+pub(crate) fn write_frame<T: AsRef<[u8]>>(
+    config: &PalsConfig,
+    data: &[T],
+    preamble: bool,
+    output: &mut impl Output,
+) -> Result<(), PalsError> {
+    let start = output.len();
 
-    let mut i = 0;
+    write_head(config, data.iter().map(|i| i.as_ref().len()), preamble, output)?;
 
-    while i < data.len() && data[i] != 0 {
-        let len = data[i] as usize;
-        if len > data.len() - i - 1 {
-            return Err("Input data contains a slice that is too large to be deserialized.".to_string());
-        }
-        lengths.push(len - 1);
-        i += 1;
+    // Loop through the input data and add each slice to the output vector.
+    for i in data {
+        output.put(i.as_ref()); // Add the slice to the output vector.
     }
 
-    if i == data.len() {
-        return Err("Input data is missing the terminating null byte.".to_string());
-    }
+    let frame = config.checksum.frame().then(|| {
+        let mut hasher = Crc32c::new();
+        hasher.update(&output.written()[start..]);
+        hasher
+    });
+    write_trailer(config.checksum, data.iter().map(|i| i.as_ref()), frame, output);
 
-    i += 1;
+    Ok(())
+}
 
-    if i + lengths.iter().sum::<usize>() > data.len() {
-        return Err("Input data is incomplete.".to_string());
+// Append the optional preamble and the length table for segments of the
+// given lengths to `output`. Every encoder starts its frames through here.
+// Error offsets are relative to the start of the frame, and on failure
+// `output` is cut back to where it was.
+pub(crate) fn write_head(
+    config: &PalsConfig,
+    lengths: impl ExactSizeIterator<Item = usize>,
+    preamble: bool,
+    output: &mut impl Output,
+) -> Result<(), PalsError> {
+    let start = output.len();
+
+    if preamble {
+        output.put(&Preamble::new(*config).to_bytes());
     }
 
-    for j in lengths {
-        output.push(data[i..(i + j)].to_vec());
-        i += j;
+    // Start the frame with the length table.
+    let base = output.len() - start;
+    if let Err(e) = config.write_lengths(lengths, output) {
+        output.truncate(start);
+        return Err(e.shifted(base));
     }
 
-    Ok(output)
+    Ok(())
 }
 
-// Deserialize a byte slice into a vector of byte vectors.
-// Each byte vector represents a slice of the input data.
-pub fn deserialize_be(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
-    let mut output = Vec::new(); // Initialize an empty vector to hold the output byte vectors.
-    let mut lengths = Vec::new(); // Initialize an empty vector to hold the lengths of the input slices.
+// Check that `write_head` would accept segments of the given lengths, without
+// writing anything, so the size of the frame is only worked out for frames
+// that can be encoded.
+pub(crate) fn check_head(config: &PalsConfig, lengths: impl ExactSizeIterator<Item = usize>) -> Result<(), PalsError> {
+    write_head(config, lengths, false, &mut CountOutput::default())
+}
 
-    let mut i = 0; // Initialize a counter variable to keep track of the current position in the input data.
+// Decode the frame in `data` whose length table starts at `start`; anything
+// before that is taken to be the preamble. Returns the segments and the
+// offset just past the end of the frame. Error offsets are relative to `data`.
+pub(crate) fn decode_frame<'a>(
+    config: &PalsConfig,
+    data: &'a [u8],
+    start: usize,
+) -> Result<(Vec<&'a [u8]>, usize), PalsError> {
+    let (lengths, header_len) = config.read_header(&data[start..]).map_err(|e| e.shifted(start))?;
+    let (segments, end) = split_payload(data, start + header_len, &lengths)?;
+    let frame_end = check_trailer(config, data, end, segments.iter().copied())?;
+
+    Ok((segments, frame_end))
+}
 
-    // Loop through the input data until we reach the end or encounter a zero-length slice.
-    while i + 8 <= data.len() && (data[i..(i + 8)] != [0; 8]) {
-        // Read the length of the next slice from the input data.
-        let len = u64::from_be_bytes(data[i..(i + 8)].try_into().unwrap()) - 1;
+// Same as `decode_frame`, but returns the position of every segment in `data`
+// instead of borrowing it, for frames that keep hold of their buffer.
+pub(crate) fn decode_ranges(
+    config: &PalsConfig,
+    data: &[u8],
+    start: usize,
+) -> Result<(Vec<Range<usize>>, usize), PalsError> {
+    let (lengths, header_len) = config.read_header(&data[start..]).map_err(|e| e.shifted(start))?;
+    let mut ranges = Vec::with_capacity(lengths.len());
+    let mut i = start + header_len;
+
+    for (segment, len) in lengths.into_iter().enumerate() {
+        let end = segment_end(data, i, segment, len)?;
+        ranges.push(i..end);
+        i = end;
+    }
 
-        // Check if the length is too large to be deserialized.
-        if len > u64::MAX - 1 {
-            return Err("Input data contains a slice that is too large to be deserialized.".to_string());
-        }
+    let frame_end = check_trailer(config, data, i, ranges.iter().map(|i| &data[i.clone()]))?;
 
-        // Add the length to the lengths vector.
-        lengths.push(len as usize);
+    Ok((ranges, frame_end))
+}
+
+// Decode the frame in `data`, which must end exactly where `data` ends.
+pub(crate) fn decode_frame_exact<'a>(config: &PalsConfig, data: &'a [u8]) -> Result<Vec<&'a [u8]>, PalsError> {
+    let (segments, end) = decode_frame(config, data, 0)?;
 
-        // Move the counter variable to the start of the next slice.
-        i += 8;
+    if end != data.len() {
+        return Err(PalsError::TrailingBytes { offset: end, segment: segments.len(), expected: end, available: data.len() });
     }
 
-    i += 8; // Move the counter variable past the zero-length slice.
+    Ok(segments)
+}
 
-    // Check if the combined lengths of the slices is greater than the length of the input data.
-    if i + lengths.iter().sum::<usize>() > data.len() {
-        return Err("Input data is incomplete.".to_string());
+// Cut the payload starting at `start` into the segments listed in `lengths`.
+// Returns the segments and the offset just past the last one.
+// On failure the error points at the first segment that does not fit.
+fn split_payload<'a>(data: &'a [u8], start: usize, lengths: &[usize]) -> Result<(Vec<&'a [u8]>, usize), PalsError> {
+    let mut output = Vec::with_capacity(lengths.len());
+    let mut i = start;
+
+    for (segment, len) in lengths.iter().enumerate() {
+        let end = segment_end(data, i, segment, *len)?;
+        output.push(&data[i..end]);
+        i = end;
     }
 
-    // Loop through the lengths vector and extract the corresponding slices from the input data.
-    for j in lengths {
-        output.push(data[i..(i + j)].to_vec()); // Add the slice to the output vector.
-        i += j; // Move the counter variable to the start of the next slice.
+    Ok((output, i))
+}
+
+// The offset just past the segment of `len` bytes that starts at `offset`,
+// as long as `data` holds all of it.
+fn segment_end(data: &[u8], offset: usize, segment: usize, len: usize) -> Result<usize, PalsError> {
+    let end = offset.checked_add(len).ok_or(PalsError::LengthOverflow {
+        offset,
+        segment,
+        expected: usize::MAX,
+        available: data.len() - offset,
+    })?;
+
+    if end > data.len() {
+        return Err(PalsError::TruncatedPayload { offset, segment, expected: len, available: data.len() - offset });
     }
 
-    Ok(output) // Return the output vector.
+    Ok(end)
+}
+
+// Check the checksum trailer that starts at `end`, just past `segments`.
+// Returns the offset just past the end of the frame.
+fn check_trailer<'a>(
+    config: &PalsConfig,
+    data: &[u8],
+    end: usize,
+    segments: impl ExactSizeIterator<Item = &'a [u8]>,
+) -> Result<usize, PalsError> {
+    let trailer_len = config.checksum.trailer_len(segments.len());
+    if data.len() - end < trailer_len {
+        return Err(PalsError::TruncatedPayload {
+            offset: end,
+            segment: segments.len(),
+            expected: trailer_len,
+            available: data.len() - end,
+        });
+    }
+
+    let frame_end = end + trailer_len;
+    let segment_crcs = segments.map(crc32c);
+    verify_trailer(config.checksum, segment_crcs, &data[end..frame_end], end, || crc32c(&data[..(frame_end - 4)]))?;
+
+    Ok(frame_end)
 }
 
 #[cfg(test)]
@@ -162,10 +532,36 @@ mod tests {
     fn test_serialize_be_empty_input() {
         let data = Vec::<Vec<u8>>::new();
 
-        let result = serialize_be(&data.iter().map(|v| v.as_slice()).collect::<Vec<_>>());
+        let serialized = serialize_be(&data.iter().map(|v| v.as_slice()).collect::<Vec<_>>()).unwrap();
+
+        assert_eq!(serialized, [0; 8]);
+        assert_eq!(deserialize_be(&serialized).unwrap(), data);
+    }
+
+    #[test]
+    fn test_serialize_empty_input_every_config() {
+        for config in [PalsConfig::LE, PalsConfig::BE, PalsConfig::VARINT, PalsConfig::new(Width::U16, ByteOrder::Big)] {
+            for checksum in [Checksum::None, Checksum::Both] {
+                let config = config.with_checksum(checksum);
+
+                let serialized = serialize_with(&config, &[]).unwrap();
+                assert_eq!(deserialize_with(&config, &serialized).unwrap(), Vec::<Vec<u8>>::new());
+                assert_eq!(deserialize_with_strict(&config, &serialized).unwrap(), Vec::<Vec<u8>>::new());
+                assert_eq!(deserialize_with_partial(&config, &serialized).unwrap(), (vec![], serialized.len()));
+
+                let serialized = serialize_with_preamble(&config, &[]).unwrap();
+                assert_eq!(deserialize_auto(&serialized).unwrap(), Vec::<Vec<u8>>::new());
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize_empty_input_rejected_when_strict() {
+        let config = PalsConfig::BE.with_reject_empty(true);
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Input data is empty.");
+        assert!(matches!(serialize_with(&config, &[]), Err(PalsError::EmptyInput { .. })));
+        assert_eq!(serialize_with(&config, &[b"x"]).unwrap(), serialize_be(&[b"x"]).unwrap());
+        assert_eq!(deserialize_with(&config, &[0; 8]).unwrap(), Vec::<Vec<u8>>::new());
     }
 
     #[test]
@@ -174,11 +570,33 @@ mod tests {
 
         let result = deserialize_be(&data);
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Input data is incomplete.");
+        assert!(matches!(
+            result,
+            Err(PalsError::MissingTerminator { offset: 8, segment: 1, expected: 8, available: 3 })
+        ));
+        assert!(result.unwrap_err().is_incomplete());
     }
 
+    #[test]
+    fn test_deserialize_be_truncated_payload() {
+        let data = vec![0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2];
+
+        let result = deserialize_be(&data);
+
+        assert!(matches!(
+            result,
+            Err(PalsError::TruncatedPayload { offset: 16, segment: 0, expected: 3, available: 2 })
+        ));
+    }
 
+    #[test]
+    fn test_deserialize_be_length_overflow() {
+        let data = vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+
+        let result = deserialize_be(&data);
+
+        assert!(matches!(result, Err(PalsError::LengthOverflow { segment: 0, .. })));
+    }
 
     #[test]
     fn test_serialize_deserialize_le() {
@@ -200,25 +618,27 @@ mod tests {
     fn test_serialize_le_empty_input() {
         let data = Vec::<Vec<u8>>::new();
 
-        let result = serialize_le(&data.iter().map(|v| v.as_slice()).collect::<Vec<_>>());
+        let serialized = serialize_le(&data.iter().map(|v| v.as_slice()).collect::<Vec<_>>()).unwrap();
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Input data is empty.");
+        assert_eq!(serialized, [0]);
+        assert_eq!(deserialize_le(&serialized).unwrap(), data);
     }
 
     #[test]
     fn test_serialize_le_large_input() {
-        let data = vec![vec![0; 1_000_000_000]];
+        let data = [vec![0; 1_000_000_000]];
 
         let result = serialize_le(&data.iter().map(|v| v.as_slice()).collect::<Vec<_>>());
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Input data contains a slice that is too large to be serialized.");
+        assert!(matches!(
+            result,
+            Err(PalsError::SegmentTooLarge { segment: 0, expected: 1_000_000_000, available: 254, .. })
+        ));
     }
 
     #[test]
     fn test_deserialize_le_incomplete_input() {
-        let data = vec![
+        let data = [
             vec![1, 2, 3],
             vec![4, 5, 6, 7],
             vec![8, 9],
@@ -230,7 +650,162 @@ mod tests {
 
         let result = deserialize_le(&serialized[0..(serialized.len() - 1)]);
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Input data is incomplete.");
+        assert!(matches!(result, Err(PalsError::TruncatedPayload { .. })));
+    }
+
+    #[test]
+    fn test_deserialize_ref_borrows_input() {
+        let data: [&[u8]; 3] = [b"header", b"", b"body"];
+
+        let serialized = serialize_be(&data).unwrap();
+        let deserialized = deserialize_be_ref(&serialized).unwrap();
+
+        assert_eq!(deserialized, data);
+        assert!(std::ptr::eq(deserialized[2].as_ptr(), serialized[serialized.len() - 4..].as_ptr()));
+
+        let serialized = serialize_le(&data).unwrap();
+        let deserialized = deserialize_le_ref(&serialized).unwrap();
+
+        assert_eq!(deserialized, data);
+        assert_eq!(deserialize_le(&serialized).unwrap(), data);
+    }
+
+    #[test]
+    fn test_deserialize_ref_incomplete_input() {
+        let serialized = serialize_be(&[b"abc", b"de"]).unwrap();
+
+        let result = deserialize_be_ref(&serialized[..serialized.len() - 1]);
+
+        assert!(matches!(
+            result,
+            Err(PalsError::TruncatedPayload { offset: 27, segment: 1, expected: 2, available: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_partial_walks_concatenated_frames() {
+        let mut buffer = serialize_be(&[b"first"]).unwrap();
+        buffer.extend(serialize_be(&[b"second", b"frame"]).unwrap());
+
+        let (first, consumed) = deserialize_be_partial(&buffer).unwrap();
+        assert_eq!(first, [b"first"]);
+        assert_eq!(consumed, 21);
+
+        let (second, rest) = deserialize_be_partial(&buffer[consumed..]).unwrap();
+        assert_eq!(second, [&b"second"[..], b"frame"]);
+        assert_eq!(consumed + rest, buffer.len());
+
+        let mut buffer = serialize_le(&[b"a"]).unwrap();
+        buffer.extend_from_slice(b"tail");
+        assert_eq!(deserialize_le_partial(&buffer).unwrap(), (vec![&b"a"[..]], 3));
+        assert_eq!(deserialize_varint_partial(&serialize_varint(&[b"v"]).unwrap()).unwrap().1, 3);
+
+        let config = PalsConfig::LE.with_checksum(Checksum::Both);
+        let mut buffer = serialize_with_preamble(&config, &[b"a"]).unwrap();
+        let len = buffer.len();
+        buffer.extend_from_slice(b"tail");
+        assert_eq!(deserialize_auto_partial(&buffer).unwrap(), (vec![&b"a"[..]], len));
+    }
+
+    #[test]
+    fn test_deserialize_strict_rejects_trailing_bytes() {
+        let serialized = serialize_be(&[b"abc"]).unwrap();
+        assert_eq!(deserialize_be_strict(&serialized).unwrap(), [b"abc"]);
+
+        let mut padded = serialized.clone();
+        padded.push(0);
+        assert!(matches!(
+            deserialize_be_strict(&padded),
+            Err(PalsError::TrailingBytes { offset: 19, segment: 1, expected: 19, available: 20 })
+        ));
+
+        let mut padded = serialize_le(&[b"abc"]).unwrap();
+        padded.extend_from_slice(&serialize_le(&[b"def"]).unwrap());
+        assert!(matches!(deserialize_le_strict(&padded), Err(PalsError::TrailingBytes { offset: 5, .. })));
+        assert!(matches!(deserialize_varint_strict(&padded), Err(PalsError::TrailingBytes { offset: 5, .. })));
+    }
+
+    #[test]
+    fn test_encoded_len_matches_serialize() {
+        let data: [&[u8]; 3] = [b"abc", &[7; 300], b""];
+
+        for width in [Width::U16, Width::U32, Width::U64, Width::Varint] {
+            for checksum in [Checksum::None, Checksum::Segments, Checksum::Both] {
+                let config = PalsConfig::new(width, ByteOrder::Little).with_checksum(checksum);
+                assert_eq!(encoded_len_with(&config, &data), serialize_with(&config, &data).unwrap().len());
+            }
+        }
+
+        assert_eq!(encoded_len_le(&[b"ab"]), 4);
+        assert_eq!(encoded_len_be(&[b"ab"]), 18);
+        assert_eq!(encoded_len_varint(&[&[0; 200]]), 203);
+        assert_eq!(encoded_len_be(&[]), 8);
+    }
+
+    #[test]
+    fn test_serialize_into_buffer() {
+        let data: [&[u8]; 2] = [b"hello", b"world"];
+        let mut buffer = [0xff; 64];
+
+        let len = serialize_be_into(&data, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], serialize_be(&data).unwrap());
+        assert_eq!(buffer[len], 0xff);
+
+        let len = serialize_le_into(&data, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], serialize_le(&data).unwrap());
+
+        let config = PalsConfig::VARINT.with_checksum(Checksum::Both);
+        let len = serialize_with_into(&config, &data, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], serialize_with(&config, &data).unwrap());
+
+        let mut exact = [0; 13];
+        assert_eq!(serialize_varint_into(&data, &mut exact), Ok(13));
+    }
+
+    #[test]
+    fn test_serialize_into_buffer_too_small() {
+        let data: [&[u8]; 2] = [b"hello", b"world"];
+        let mut buffer = [0; 25];
+
+        assert!(matches!(
+            serialize_be_into(&data, &mut buffer),
+            Err(PalsError::BufferTooSmall { expected: 34, available: 25, .. })
+        ));
+        assert_eq!(buffer, [0; 25]);
+
+        assert!(matches!(serialize_le_into(&[&[0; 300]], &mut [0; 400]), Err(PalsError::SegmentTooLarge { .. })));
+
+        // A frame that cannot be encoded is reported as such, even if it would not fit either.
+        let mut buffer = [0; 10];
+        assert!(matches!(
+            serialize_le_into(&[b"ab", &[0; 300]], &mut buffer),
+            Err(PalsError::SegmentTooLarge { offset: 1, segment: 1, .. })
+        ));
+        let config = PalsConfig::LE.with_reject_empty(true);
+        assert!(matches!(serialize_with_into(&config, &[], &mut []), Err(PalsError::EmptyInput { .. })));
+        assert_eq!(buffer, [0; 10]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_serialize_append() {
+        let mut output = b"log:".to_vec();
+
+        assert_eq!(serialize_be_append(&[b"a"], &mut output), Ok(17));
+        assert_eq!(serialize_le_append(&[b"b"], &mut output), Ok(3));
+        assert_eq!(serialize_varint_append(&[b"c"], &mut output), Ok(3));
+
+        assert_eq!(&output[..4], b"log:");
+        assert_eq!(deserialize_be_partial(&output[4..]).unwrap(), (vec![&b"a"[..]], 17));
+        assert_eq!(deserialize_le_strict(&output[21..24]).unwrap(), [b"b"]);
+        assert_eq!(deserialize_varint_strict(&output[24..]).unwrap(), [b"c"]);
+
+        let config = PalsConfig::BE.with_checksum(Checksum::Frame);
+        let start = output.len();
+        serialize_with_append(&config, &[b"checked"], &mut output).unwrap();
+        assert_eq!(&output[start..], serialize_with(&config, &[b"checked"]).unwrap());
+
+        let len = output.len();
+        assert!(serialize_le_append(&[b"ok", &[0; 300]], &mut output).is_err());
+        assert_eq!(output.len(), len);
+    }
+}
@@ -0,0 +1,115 @@
+use alloc::vec::Vec;
+
+// Somewhere to write an encoded frame: either a growable `Vec<u8>` or
+// `BytesMut`, or a caller-supplied slice that has already been checked to be
+// large enough.
+// Keeping the encoders generic over this lets `serialize_*_into` fill a fixed
+// buffer without allocating.
+pub(crate) trait Output {
+    // The number of bytes written so far.
+    fn len(&self) -> usize;
+
+    // Append `data` after the bytes written so far.
+    fn put(&mut self, data: &[u8]);
+
+    // Drop everything written after the first `len` bytes.
+    fn truncate(&mut self, len: usize);
+
+    // The bytes written so far.
+    fn written(&self) -> &[u8];
+}
+
+impl Output for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.extend_from_slice(data);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn written(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Output for bytes::BytesMut {
+    fn len(&self) -> usize {
+        bytes::BytesMut::len(self)
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.extend_from_slice(data);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        bytes::BytesMut::truncate(self, len);
+    }
+
+    fn written(&self) -> &[u8] {
+        self
+    }
+}
+
+// Counts the bytes written without keeping them, so a frame can be checked
+// before there is anywhere to put it. `written` is always empty, so it cannot
+// take a frame checksum.
+#[derive(Default)]
+pub(crate) struct CountOutput {
+    len: usize,
+}
+
+impl Output for CountOutput {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.len += data.len();
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn written(&self) -> &[u8] {
+        &[]
+    }
+}
+
+// Writes into a fixed slice from the start. Writing past its end panics, so
+// callers must check the size of the frame first.
+pub(crate) struct SliceOutput<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceOutput<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        SliceOutput { buf, len: 0 }
+    }
+}
+
+impl Output for SliceOutput<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.buf[self.len..(self.len + data.len())].copy_from_slice(data);
+        self.len += data.len();
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
@@ -0,0 +1,223 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+use crate::{decode_ranges, PalsConfig, PalsError};
+
+// A validated frame that owns the buffer it was parsed from.
+// Unlike `deserialize_*`, which copies every segment into a `Vec` of its own,
+// the payload stays where it is and the frame only keeps a table of ranges
+// into it. Decoding allocates the parsed length table and the table of ranges,
+// however many segments there are.
+//
+// The buffer can be anything that is `AsRef<[u8]>`: a `Vec<u8>` is taken over
+// as it is, while an `Arc<[u8]>` or a `Bytes` makes the frame cheap to clone
+// and share between threads. With the `bytes` feature, a frame backed by
+// `Bytes` also hands out segments as `Bytes` sharing that buffer.
+#[derive(Debug, Clone)]
+pub struct OwnedFrame<B = Vec<u8>> {
+    data: B,
+    ranges: Vec<Range<usize>>,
+    end: usize,
+}
+
+impl<B: AsRef<[u8]>> OwnedFrame<B> {
+    // Parse a frame laid out as described by `config` at the start of `data`.
+    // Bytes after the end of the frame are ignored, but stay in the buffer.
+    pub fn parse_with(config: &PalsConfig, data: B) -> Result<Self, PalsError> {
+        let (ranges, end) = decode_ranges(config, data.as_ref(), 0)?;
+        Ok(OwnedFrame { data, ranges, end })
+    }
+
+    // Parse a frame written by `serialize_be`.
+    pub fn parse_be(data: B) -> Result<Self, PalsError> {
+        OwnedFrame::parse_with(&PalsConfig::BE, data)
+    }
+
+    // Parse a frame written by `serialize_le`.
+    pub fn parse_le(data: B) -> Result<Self, PalsError> {
+        OwnedFrame::parse_with(&PalsConfig::LE, data)
+    }
+
+    // Parse a frame written by `serialize_varint`.
+    pub fn parse_varint(data: B) -> Result<Self, PalsError> {
+        OwnedFrame::parse_with(&PalsConfig::VARINT, data)
+    }
+
+    // The number of segments in the frame.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    // The segment at `index`, or `None` if there are not that many segments.
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let range = self.ranges.get(index)?;
+        Some(&self.data.as_ref()[range.clone()])
+    }
+
+    // Iterate over the segments in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> + '_ {
+        let data = self.data.as_ref();
+        self.ranges.iter().map(move |i| &data[i.clone()])
+    }
+
+    // The position of the segment at `index` in the buffer.
+    pub fn payload_range(&self, index: usize) -> Option<Range<usize>> {
+        self.ranges.get(index).cloned()
+    }
+
+    // The number of buffer bytes the frame takes up, including its checksums.
+    pub fn frame_len(&self) -> usize {
+        self.end
+    }
+
+    // The whole buffer the frame was parsed from.
+    pub fn buffer(&self) -> &B {
+        &self.data
+    }
+
+    // Give the buffer back, dropping the table of segments.
+    pub fn into_inner(self) -> B {
+        self.data
+    }
+
+    // Move the buffer into a `Bytes` so that segments can be handed out on
+    // their own. Converting a `Vec<u8>` or `Box<[u8]>` does not copy it.
+    #[cfg(feature = "bytes")]
+    pub fn into_shared(self) -> OwnedFrame<Bytes>
+    where
+        B: Into<Bytes>,
+    {
+        OwnedFrame { data: self.data.into(), ranges: self.ranges, end: self.end }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl OwnedFrame<Bytes> {
+    // The segment at `index` as a `Bytes` sharing the frame's buffer, which
+    // stays alive for as long as any segment handed out from it.
+    pub fn get_bytes(&self, index: usize) -> Option<Bytes> {
+        let range = self.ranges.get(index)?;
+        Some(self.data.slice(range.clone()))
+    }
+
+    // Iterate over the segments as `Bytes` sharing the frame's buffer.
+    pub fn iter_bytes(&self) -> impl ExactSizeIterator<Item = Bytes> + '_ {
+        self.ranges.iter().map(|i| self.data.slice(i.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_with, serialize_be, serialize_le, serialize_varint, serialize_with};
+    use crate::{ByteOrder, Checksum, Width};
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_owned_frame_takes_over_vec() {
+        let data: Vec<Vec<u8>> = (0..40u8).map(|i| vec![i; i as usize]).collect();
+        let refs: Vec<&[u8]> = data.iter().map(|i| i.as_slice()).collect();
+        let serialized = serialize_be(&refs).unwrap();
+        let ptr = serialized.as_ptr();
+        let len = serialized.len();
+
+        let frame = OwnedFrame::parse_be(serialized).unwrap();
+
+        assert_eq!(frame.len(), 40);
+        assert_eq!(frame.get(7), Some(&[7u8; 7][..]));
+        assert_eq!(frame.get(40), None);
+        assert_eq!(frame.iter().collect::<Vec<_>>(), refs);
+        assert_eq!(frame.frame_len(), len);
+        assert_eq!(&frame.buffer()[frame.payload_range(7).unwrap()], &[7; 7]);
+
+        // The segments point into the original allocation.
+        assert_eq!(frame.get(0).unwrap().as_ptr(), ptr.wrapping_add(41 * 8));
+        let buffer = frame.into_inner();
+        assert_eq!(buffer.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_owned_frame_matches_deserialize() {
+        let data: [&[u8]; 4] = [b"abc", b"", &[7; 300], b"z"];
+
+        for width in [Width::U16, Width::U64, Width::Varint] {
+            for checksum in [Checksum::None, Checksum::Segments, Checksum::Both] {
+                let config = PalsConfig::new(width, ByteOrder::Big).with_checksum(checksum);
+                let serialized = serialize_with(&config, &data).unwrap();
+
+                let frame = OwnedFrame::parse_with(&config, serialized.as_slice()).unwrap();
+                assert_eq!(frame.iter().collect::<Vec<_>>(), data);
+                assert_eq!(frame.frame_len(), serialized.len());
+
+                let mut truncated = serialized.clone();
+                truncated.pop();
+                assert_eq!(
+                    OwnedFrame::parse_with(&config, truncated.as_slice()).unwrap_err(),
+                    deserialize_with(&config, &truncated).unwrap_err()
+                );
+            }
+        }
+
+        let serialized = serialize_varint(&data).unwrap();
+        let frame = OwnedFrame::parse_varint(serialized).unwrap();
+        assert_eq!(frame.payload_range(2), Some(9..309));
+    }
+
+    #[test]
+    fn test_owned_frame_shared_across_threads() {
+        let serialized: Arc<[u8]> = serialize_le(&[b"ab", b"", b"cd"]).unwrap().into();
+        let frame = OwnedFrame::parse_le(serialized).unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frame = frame.clone();
+                std::thread::spawn(move || frame.iter().map(|i| i.to_vec()).collect::<Vec<_>>())
+            })
+            .collect();
+
+        for i in handles {
+            assert_eq!(i.join().unwrap(), [b"ab".to_vec(), vec![], b"cd".to_vec()]);
+        }
+        assert_eq!(frame.get(2).unwrap().as_ptr(), frame.buffer()[6..].as_ptr());
+    }
+
+    #[test]
+    fn test_owned_frame_errors_and_trailing_bytes() {
+        let config = PalsConfig::BE.with_checksum(Checksum::Segments);
+        let mut serialized = serialize_with(&config, &[b"abc"]).unwrap();
+        let len = serialized.len();
+        serialized.extend_from_slice(b"next");
+
+        let frame = OwnedFrame::parse_with(&config, serialized.clone()).unwrap();
+        assert_eq!(frame.frame_len(), len);
+        assert_eq!(frame.buffer().len(), len + 4);
+
+        serialized[16] ^= 1;
+        assert!(matches!(OwnedFrame::parse_with(&config, serialized), Err(PalsError::SegmentChecksumMismatch { .. })));
+        assert!(matches!(OwnedFrame::parse_be(&[2u8, 0][..]), Err(PalsError::MissingTerminator { .. })));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_owned_frame_bytes_handles() {
+        let serialized = serialize_le(&[b"hello", b"world"]).unwrap();
+        let ptr = serialized.as_ptr();
+
+        let frame = OwnedFrame::parse_le(serialized).unwrap().into_shared();
+        let world = frame.get_bytes(1).unwrap();
+        let all: Vec<Bytes> = frame.iter_bytes().collect();
+        drop(frame);
+
+        assert_eq!(world, &b"world"[..]);
+        assert_eq!(all, [&b"hello"[..], &b"world"[..]]);
+        assert_eq!(all[0].as_ptr(), ptr.wrapping_add(3));
+        assert!(std::thread::spawn(move || world.len()).join().is_ok());
+    }
+}
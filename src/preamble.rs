@@ -0,0 +1,205 @@
+use alloc::vec::Vec;
+
+use crate::{ByteOrder, Checksum, PalsConfig, PalsError, Width};
+
+// The optional preamble that makes a frame self-describing.
+// It is 8 bytes long and placed directly in front of the length table:
+//
+//     bytes 0..4  the magic bytes "PALS"
+//     byte  4     the format version, currently 1
+//     byte  5     layout flags: bits 0-2 hold the width (0 = u8, 1 = u16,
+//                 2 = u32, 3 = u64, 4 = varint), bit 3 is set for big-endian
+//                 lengths, bits 4-7 are reserved and must be zero
+//     bytes 6..8  feature bits as a big-endian u16: bit 0 is set for segment
+//                 checksums, bit 1 for a frame checksum, the others are
+//                 reserved and rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Preamble {
+    pub config: PalsConfig,
+    pub features: u16,
+}
+
+impl Preamble {
+    // The magic bytes every preamble starts with.
+    pub const MAGIC: [u8; 4] = *b"PALS";
+
+    // The newest format version this crate can read and the one it writes.
+    pub const VERSION: u8 = 1;
+
+    // The size of an encoded preamble in bytes.
+    pub const LEN: usize = 8;
+
+    // The feature bit for segment checksums.
+    pub const SEGMENT_CHECKSUMS: u16 = 0b01;
+
+    // The feature bit for a frame checksum.
+    pub const FRAME_CHECKSUM: u16 = 0b10;
+
+    // The feature bits this crate understands.
+    pub const KNOWN_FEATURES: u16 = Preamble::SEGMENT_CHECKSUMS | Preamble::FRAME_CHECKSUM;
+
+    const BIG_ENDIAN: u8 = 0b1000;
+    const WIDTH_MASK: u8 = 0b0111;
+
+    // Create a preamble for frames laid out as described by `config`.
+    pub fn new(config: PalsConfig) -> Self {
+        let mut features = 0;
+        if config.checksum.segments() {
+            features |= Preamble::SEGMENT_CHECKSUMS;
+        }
+        if config.checksum.frame() {
+            features |= Preamble::FRAME_CHECKSUM;
+        }
+        Preamble { config, features }
+    }
+
+    // Append the encoded preamble to `output`.
+    pub fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.to_bytes());
+    }
+
+    // The encoded preamble.
+    pub(crate) fn to_bytes(self) -> [u8; Preamble::LEN] {
+        let width = match self.config.width {
+            Width::U8 => 0,
+            Width::U16 => 1,
+            Width::U32 => 2,
+            Width::U64 => 3,
+            Width::Varint => 4,
+        };
+        let order = match self.config.order {
+            ByteOrder::Little => 0,
+            ByteOrder::Big => Preamble::BIG_ENDIAN,
+        };
+
+        let features = self.features.to_be_bytes();
+        let magic = Preamble::MAGIC;
+        [magic[0], magic[1], magic[2], magic[3], Preamble::VERSION, width | order, features[0], features[1]]
+    }
+
+    // Read the preamble at the start of `data`.
+    pub fn read(data: &[u8]) -> Result<Preamble, PalsError> {
+        // Check the magic bytes first, so unrelated input is reported as such
+        // even if it happens to be short.
+        let magic = data.len().min(Preamble::MAGIC.len());
+        if data[..magic] != Preamble::MAGIC[..magic] {
+            return Err(PalsError::BadMagic { offset: 0, segment: 0, expected: Preamble::MAGIC.len(), available: magic });
+        }
+
+        if data.len() < Preamble::LEN {
+            return Err(PalsError::TruncatedPreamble {
+                offset: 0,
+                segment: 0,
+                expected: Preamble::LEN,
+                available: data.len(),
+            });
+        }
+
+        if data[4] == 0 || data[4] > Preamble::VERSION {
+            return Err(PalsError::UnsupportedVersion {
+                offset: 4,
+                segment: 0,
+                expected: Preamble::VERSION as usize,
+                available: data[4] as usize,
+            });
+        }
+
+        let flags = data[5];
+        let width = match flags & Preamble::WIDTH_MASK {
+            0 => Some(Width::U8),
+            1 => Some(Width::U16),
+            2 => Some(Width::U32),
+            3 => Some(Width::U64),
+            4 => Some(Width::Varint),
+            _ => None,
+        };
+
+        let width = match width {
+            Some(width) if flags & !(Preamble::WIDTH_MASK | Preamble::BIG_ENDIAN) == 0 => width,
+            _ => {
+                return Err(PalsError::UnsupportedFlags {
+                    offset: 5,
+                    segment: 0,
+                    expected: (Preamble::WIDTH_MASK | Preamble::BIG_ENDIAN) as usize,
+                    available: flags as usize,
+                })
+            }
+        };
+        let order = if flags & Preamble::BIG_ENDIAN != 0 { ByteOrder::Big } else { ByteOrder::Little };
+
+        let features = u16::from_be_bytes([data[6], data[7]]);
+        if features & !Preamble::KNOWN_FEATURES != 0 {
+            return Err(PalsError::UnsupportedFlags {
+                offset: 6,
+                segment: 0,
+                expected: Preamble::KNOWN_FEATURES as usize,
+                available: features as usize,
+            });
+        }
+
+        let checksum =
+            Checksum::from_parts(features & Preamble::SEGMENT_CHECKSUMS != 0, features & Preamble::FRAME_CHECKSUM != 0);
+
+        Ok(Preamble { config: PalsConfig::new(width, order).with_checksum(checksum), features })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_auto, serialize_be, serialize_with_preamble};
+
+    #[test]
+    fn test_deserialize_auto_every_config() {
+        let data: [&[u8]; 3] = [b"header", b"", b"body"];
+
+        for width in [Width::U8, Width::U16, Width::U32, Width::U64, Width::Varint] {
+            for order in [ByteOrder::Little, ByteOrder::Big] {
+                let config = PalsConfig::new(width, order);
+
+                let serialized = serialize_with_preamble(&config, &data).unwrap();
+
+                assert_eq!(Preamble::read(&serialized).unwrap(), Preamble::new(config));
+                assert_eq!(deserialize_auto(&serialized).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_preamble_layout() {
+        let serialized = serialize_with_preamble(&PalsConfig::BE, &[b"x"]).unwrap();
+
+        assert_eq!(&serialized[..8], b"PALS\x01\x0b\x00\x00");
+        assert_eq!(&serialized[8..], serialize_be(&[b"x"]).unwrap());
+    }
+
+    #[test]
+    fn test_deserialize_auto_rejects_bad_preamble() {
+        let serialized = serialize_with_preamble(&PalsConfig::LE, &[b"abc"]).unwrap();
+
+        assert!(matches!(deserialize_auto(&serialize_be(&[b"abc"]).unwrap()), Err(PalsError::BadMagic { .. })));
+        assert!(matches!(deserialize_auto(&serialized[..6]), Err(PalsError::TruncatedPreamble { available: 6, .. })));
+
+        let mut data = serialized.clone();
+        data[4] = 2;
+        assert!(matches!(deserialize_auto(&data), Err(PalsError::UnsupportedVersion { expected: 1, available: 2, .. })));
+
+        let mut data = serialized.clone();
+        data[5] = 0x10;
+        assert!(matches!(deserialize_auto(&data), Err(PalsError::UnsupportedFlags { offset: 5, .. })));
+
+        let mut data = serialized;
+        data[7] = 4;
+        assert!(matches!(deserialize_auto(&data), Err(PalsError::UnsupportedFlags { offset: 6, .. })));
+    }
+
+    #[test]
+    fn test_deserialize_auto_error_offsets() {
+        let serialized = serialize_with_preamble(&PalsConfig::LE, &[b"abc"]).unwrap();
+
+        assert!(matches!(
+            deserialize_auto(&serialized[..serialized.len() - 1]),
+            Err(PalsError::TruncatedPayload { offset: 10, segment: 0, expected: 3, available: 2 })
+        ));
+    }
+}
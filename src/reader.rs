@@ -0,0 +1,368 @@
+use std::io::{self, Read};
+
+use crate::checksum::{crc32c, verify_trailer, Crc32c};
+use crate::varint::MAX_VARINT_LEN;
+use crate::{DecodeLimits, PalsConfig, PalsError, Preamble, Width};
+
+// Reads PALS frames one after another from any `Read` source, such as a file
+// or a socket. The length table is read until its terminator and then exactly
+// as many payload bytes as it announces, so consecutive frames can be read
+// back-to-back from the same source.
+//
+// The length table is read in small pieces, so unbuffered sources should be
+// wrapped in a `BufReader` first.
+pub struct PalsReader<R> {
+    inner: R,
+    config: PalsConfig,
+    // Whether every frame starts with a `Preamble` that overrides `config`.
+    auto: bool,
+}
+
+impl<R: Read> PalsReader<R> {
+    // Create a reader for frames laid out as described by `config`.
+    pub fn with_config(inner: R, config: PalsConfig) -> Self {
+        PalsReader { inner, config, auto: false }
+    }
+
+    // Create a reader for frames written with a preamble, such as those from
+    // `serialize_with_preamble`. Each frame is read with the config its
+    // preamble names.
+    pub fn auto(inner: R) -> Self {
+        PalsReader { inner, config: PalsConfig::BE, auto: true }
+    }
+
+    // Reject frames that exceed `limits`. This also applies to readers created
+    // with `auto`, whose config otherwise comes from each preamble.
+    pub fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    // Create a reader for frames written by `serialize_le`.
+    pub fn le(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::LE)
+    }
+
+    // Create a reader for frames written by `serialize_be`.
+    pub fn be(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::BE)
+    }
+
+    // Create a reader for frames written by `serialize_varint`.
+    pub fn varint(inner: R) -> Self {
+        Self::with_config(inner, PalsConfig::VARINT)
+    }
+
+    // Read the next frame.
+    // Returns `Ok(None)` if the source ended cleanly before the next frame started.
+    // A frame that is cut off part way fails with `io::ErrorKind::UnexpectedEof`,
+    // and a malformed one with `io::ErrorKind::InvalidData`. In both cases the
+    // underlying `PalsError` can be recovered from the `io::Error`.
+    pub fn read_frame(&mut self) -> io::Result<Option<Vec<Vec<u8>>>> {
+        let mut preamble = [0; Preamble::LEN];
+        let mut base = 0;
+
+        if self.auto {
+            let read = read_full(&mut self.inner, &mut preamble)?;
+
+            if read == 0 {
+                return Ok(None); // The source ended between two frames.
+            }
+
+            let limits = self.config.limits;
+            self.config = Preamble::read(&preamble[..read])?.config.with_limits(limits);
+            base = Preamble::LEN;
+        }
+
+        self.read_body(&preamble[..base]).map_err(|e| match e {
+            ReadError::Io(e) => e,
+            ReadError::Pals(e) => e.shifted(base).into(),
+        })
+    }
+
+    // Read the length table, payload and checksum trailer of a frame whose
+    // preamble, if any, has already been read into `preamble`.
+    fn read_body(&mut self, preamble: &[u8]) -> Result<Option<Vec<Vec<u8>>>, ReadError> {
+        let width = self.config.width();
+        let mut header = Vec::new();
+        let mut entry = vec![0; width];
+        let mut entries = 0;
+        let mut continued = 0;
+
+        // Read the length table one entry at a time until we reach the terminator.
+        loop {
+            let read = read_full(&mut self.inner, &mut entry)?;
+
+            if read == 0 && header.is_empty() && preamble.is_empty() {
+                return Ok(None); // The source ended between two frames.
+            }
+
+            header.extend_from_slice(&entry[..read]);
+
+            if read < width || entry.iter().all(|b| *b == 0) {
+                break;
+            }
+
+            // A varint entry ends with the first byte without the continuation bit.
+            if self.config.width != Width::Varint || entry[0] & 0x80 == 0 {
+                entries += 1;
+            }
+
+            // Stop reading a table that is already too long, or a varint too overlong to be
+            // valid since no valid varint has this byte after nine continued ones; parsing
+            // it below reports what is wrong.
+            let overlong = self.config.width == Width::Varint && continued == MAX_VARINT_LEN;
+            if entries > self.config.limits.max_segments || overlong {
+                break;
+            }
+
+            continued = if entry[0] & 0x80 != 0 { continued + 1 } else { 0 };
+        }
+
+        // Let the slice parser validate the length table, so both agree on what is valid.
+        let (lengths, start) = self.config.read_header(&header)?;
+
+        let mut output = Vec::with_capacity(lengths.len());
+        let mut offset = start;
+
+        // Only hash the frame as it goes by if there is a frame checksum to check.
+        let mut hasher = self.config.checksum.frame().then(Crc32c::new);
+        if let Some(hasher) = &mut hasher {
+            hasher.update(preamble);
+            hasher.update(&header);
+        }
+
+        // Read each segment straight into its own vector.
+        for (segment, len) in lengths.into_iter().enumerate() {
+            let mut buf = Vec::new();
+            let read = (&mut self.inner).take(len as u64).read_to_end(&mut buf)?;
+
+            if read < len {
+                return Err(PalsError::TruncatedPayload { offset, segment, expected: len, available: read }.into());
+            }
+
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&buf);
+            }
+
+            output.push(buf);
+            offset += len;
+        }
+
+        let mut trailer = vec![0; self.config.checksum.trailer_len(output.len())];
+        let read = read_full(&mut self.inner, &mut trailer)?;
+
+        if read < trailer.len() {
+            return Err(PalsError::TruncatedPayload {
+                offset,
+                segment: output.len(),
+                expected: trailer.len(),
+                available: read,
+            }
+            .into());
+        }
+
+        verify_trailer(self.config.checksum, output.iter().map(|i| crc32c(i)), &trailer, offset, || {
+            let mut hasher = hasher.unwrap();
+            hasher.update(&trailer[..trailer.len() - 4]);
+            hasher.finish()
+        })?;
+
+        Ok(Some(output))
+    }
+
+    // Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    // Get a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    // Unwrap this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+// Keeps format errors apart from I/O errors until their offsets are final.
+enum ReadError {
+    Io(io::Error),
+    Pals(PalsError),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        ReadError::Io(err)
+    }
+}
+
+impl From<PalsError> for ReadError {
+    fn from(err: PalsError) -> ReadError {
+        ReadError::Pals(err)
+    }
+}
+
+impl<R: Read> Iterator for PalsReader<R> {
+    type Item = io::Result<Vec<Vec<u8>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+// Read into `buf` until it is full or the source reaches EOF.
+// Unlike `read_exact` this reports how many bytes were actually read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_be, serialize_le, serialize_varint, serialize_with, serialize_with_preamble, Checksum};
+
+    // A reader that hands out at most one byte per call.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_read_frames_back_to_back() {
+        let mut stream = serialize_be(&[b"abc", b"", b"defg"]).unwrap();
+        stream.extend(serialize_be(&[b"xyz"]).unwrap());
+
+        let mut reader = PalsReader::be(OneByte(&stream));
+
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"abc".to_vec(), vec![], b"defg".to_vec()]);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"xyz".to_vec()]);
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_frames_le_iterator() {
+        let mut stream = serialize_le(&[b"ab", b"c"]).unwrap();
+        stream.extend(serialize_le(&[b"d"]).unwrap());
+
+        let frames = PalsReader::le(stream.as_slice()).collect::<io::Result<Vec<_>>>().unwrap();
+
+        assert_eq!(frames, vec![vec![b"ab".to_vec(), b"c".to_vec()], vec![b"d".to_vec()]]);
+    }
+
+    #[test]
+    fn test_read_frames_varint() {
+        let big = vec![1; 1000];
+        let mut stream = serialize_varint(&[b"ab", &big]).unwrap();
+        stream.extend(serialize_varint(&[b""]).unwrap());
+
+        let mut reader = PalsReader::varint(OneByte(&stream));
+
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"ab".to_vec(), big]);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![Vec::new()]);
+        assert!(reader.read_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_frames_auto() {
+        let mut stream = serialize_with_preamble(&PalsConfig::LE, &[b"le"]).unwrap();
+        stream.extend(serialize_with_preamble(&PalsConfig::VARINT, &[b"varint", b""]).unwrap());
+
+        let mut reader = PalsReader::auto(OneByte(&stream));
+
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"le".to_vec()]);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"varint".to_vec(), vec![]]);
+        assert!(reader.read_frame().unwrap().is_none());
+
+        let err = PalsReader::auto(&stream[..Preamble::LEN]).read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read_frames_with_checksums() {
+        let config = PalsConfig::BE.with_checksum(Checksum::Both);
+
+        let mut stream = serialize_with(&config, &[b"abc", b"defg"]).unwrap();
+        stream.extend(serialize_with_preamble(&config, &[b"xyz"]).unwrap());
+
+        let mut reader = PalsReader::with_config(OneByte(&stream), config);
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"abc".to_vec(), b"defg".to_vec()]);
+
+        let mut reader = PalsReader::auto(OneByte(&stream[43..]));
+        assert_eq!(reader.read_frame().unwrap().unwrap(), vec![b"xyz".to_vec()]);
+
+        stream[26] ^= 0x01;
+        let err = PalsReader::with_config(stream.as_slice(), config).read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<PalsError>(),
+            Some(PalsError::SegmentChecksumMismatch { segment: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_read_truncated_frame() {
+        let stream = serialize_be(&[b"abc", b"de"]).unwrap();
+
+        for cut in 1..stream.len() {
+            let err = PalsReader::be(&stream[..cut]).read_frame().unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+            assert!(inner.is_incomplete());
+        }
+    }
+
+    #[test]
+    fn test_read_frame_limits() {
+        let limits = DecodeLimits { max_segments: 2, ..DecodeLimits::UNLIMITED };
+
+        // An endless length table is rejected after the third entry.
+        let mut reader = PalsReader::le(io::repeat(5)).limits(limits);
+        let err = reader.read_frame().unwrap_err();
+        let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+        assert!(matches!(inner, PalsError::TooManySegments { offset: 2, segment: 2, expected: 2, available: 3 }));
+
+        let stream = serialize_with_preamble(&PalsConfig::VARINT, &[b"a", b"b", b"c"]).unwrap();
+        let err = PalsReader::auto(OneByte(&stream)).limits(limits).read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+        assert!(matches!(inner, PalsError::TooManySegments { offset: 10, .. }));
+    }
+
+    #[test]
+    fn test_read_endless_varint() {
+        let limits = DecodeLimits { max_segments: 16, ..DecodeLimits::UNLIMITED };
+
+        // Continuation bytes never complete an entry, so only the varint length stops the table.
+        let err = PalsReader::varint(io::repeat(0x80)).limits(limits).read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let inner = err.get_ref().unwrap().downcast_ref::<PalsError>().unwrap();
+        assert!(matches!(inner, PalsError::InvalidVarint { offset: 0, expected: 9, available: 10, .. }));
+
+        let mut frames = crate::StreamFrameIter::varint(io::repeat(0x80));
+        assert_eq!(frames.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(frames.next().is_none());
+    }
+}
@@ -0,0 +1,311 @@
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+
+use super::Error;
+use crate::{decode_frame, PalsConfig};
+
+// Decode a value of type `T` from the PALS serde format.
+// String and byte slice fields can borrow straight from `data`.
+pub fn from_slice<'a, T: Deserialize<'a>>(data: &'a [u8]) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(data))
+}
+
+// Deserializes a single value from exactly the bytes of its encoding.
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Deserializer<'de> {
+    // Create a deserializer for the value encoded in `input`.
+    pub fn new(input: &'de [u8]) -> Self {
+        Deserializer { input }
+    }
+
+    // Take the input as a fixed-width value of `N` bytes.
+    fn fixed<const N: usize>(&self) -> Result<[u8; N], Error> {
+        self.input.try_into().map_err(|_| Error::WrongLength { expected: N, available: self.input.len() })
+    }
+
+    // Take the input apart as a nested frame that must fill it completely.
+    fn frame(&self) -> Result<Vec<&'de [u8]>, Error> {
+        let (segments, end) = decode_frame(&PalsConfig::BE, self.input, 0)?;
+
+        if end != self.input.len() {
+            return Err(Error::TrailingBytes { offset: end, available: self.input.len() - end });
+        }
+
+        Ok(segments)
+    }
+
+    // Take the input apart as a nested frame of exactly `len` segments.
+    fn frame_of(&self, len: usize) -> Result<Vec<&'de [u8]>, Error> {
+        let segments = self.frame()?;
+
+        if segments.len() != len {
+            return Err(Error::WrongSegmentCount { expected: len, available: segments.len() });
+        }
+
+        Ok(segments)
+    }
+
+    fn str(&self) -> Result<&'de str, Error> {
+        std::str::from_utf8(self.input).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error::Unsupported("deserialize_any"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.fixed::<1>()? {
+            [0] => visitor.visit_bool(false),
+            [1] => visitor.visit_bool(true),
+            [tag] => Err(Error::InvalidTag(tag)),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i8(i8::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i16(i16::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i32(i32::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i64(i64::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i128(i128::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u8(u8::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u16(u16::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u32(u32::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u64(u64::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u128(u128::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(f32::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(f64::from_be_bytes(self.fixed()?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut chars = self.str()?.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::WrongLength { expected: 1, available: self.input.len() }),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.str()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.input)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.input)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.input.split_first() {
+            Some((0, [])) => visitor.visit_none(),
+            Some((0, rest)) => Err(Error::WrongLength { expected: 1, available: 1 + rest.len() }),
+            Some((1, rest)) => visitor.visit_some(Deserializer::new(rest)),
+            Some((tag, _)) => Err(Error::InvalidTag(*tag)),
+            None => Err(Error::WrongLength { expected: 1, available: 0 }),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.fixed::<0>()?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Segments::new(self.frame()?))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Segments::new(self.frame_of(len)?))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let segments = self.frame()?;
+
+        if segments.len() % 2 != 0 {
+            return Err(Error::WrongSegmentCount { expected: segments.len() + 1, available: segments.len() });
+        }
+
+        visitor.visit_map(Segments::new(segments))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let segments = self.frame()?;
+
+        if segments.is_empty() {
+            return Err(Error::WrongSegmentCount { expected: 1, available: 0 });
+        }
+
+        visitor.visit_enum(Segments::new(segments))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit() // The value is already cut out of its frame, so there is nothing to skip.
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// Hands out the segments of a nested frame as the elements of a seq, the
+// entries of a map or the variant index and fields of an enum.
+struct Segments<'de> {
+    iter: std::vec::IntoIter<&'de [u8]>,
+}
+
+impl<'de> Segments<'de> {
+    fn new(segments: Vec<&'de [u8]>) -> Self {
+        Segments { iter: segments.into_iter() }
+    }
+
+    fn expect_remaining(&self, len: usize) -> Result<(), Error> {
+        match self.iter.len() {
+            remaining if remaining == len => Ok(()),
+            remaining => Err(Error::WrongSegmentCount { expected: len + 1, available: remaining + 1 }),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for Segments<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        self.iter.next().map(|i| seed.deserialize(Deserializer::new(i))).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Segments<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        self.iter.next().map(|i| seed.deserialize(Deserializer::new(i))).transpose()
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        // `deserialize_map` made sure every key is followed by a value.
+        seed.deserialize(Deserializer::new(self.iter.next().unwrap_or_default()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len() / 2)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for Segments<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(mut self, seed: V) -> Result<(V::Value, Self), Error> {
+        // `deserialize_enum` made sure the variant index is there.
+        let index = u32::from_be_bytes(Deserializer::new(self.iter.next().unwrap_or_default()).fixed()?);
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Segments<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.expect_remaining(0)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value, Error> {
+        self.expect_remaining(1)?;
+        seed.deserialize(Deserializer::new(self.iter.next().unwrap_or_default()))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.expect_remaining(len)?;
+        visitor.visit_seq(self)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        self.expect_remaining(fields.len())?;
+        visitor.visit_seq(self)
+    }
+}
@@ -0,0 +1,209 @@
+// A serde data format built on PALS framing.
+//
+// Values are encoded positionally, without field names or type tags:
+//
+//     bool                    1 byte, 0 or 1
+//     integers, floats        fixed width, big-endian
+//     char                    its UTF-8 bytes
+//     str, bytes              the raw bytes
+//     unit, unit structs      no bytes
+//     newtype structs         the inner value
+//     Option                  [0] for None, [1] followed by the value for Some
+//     seqs, tuples, structs   a nested frame with one segment per element
+//     maps                    a nested frame with a key and a value segment per entry
+//     enums                   a nested frame of the u32 variant index followed
+//                             by one segment per field
+//
+// Nested frames use the `serialize_be` layout, so a struct encoded with
+// `to_vec` can be taken apart with `deserialize_be` and vice versa.
+// Because the format is not self-describing, `deserialize_any` is unsupported.
+
+mod de;
+mod ser;
+
+use std::fmt;
+
+use crate::PalsError;
+
+pub use de::{from_slice, Deserializer};
+pub use ser::{to_vec, Serializer};
+
+// The error type of the serde format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    // A nested frame could not be encoded or decoded.
+    Frame(PalsError),
+    // A fixed-width value did not have the expected number of bytes.
+    WrongLength { expected: usize, available: usize },
+    // A nested frame had a different number of segments than the type needs.
+    WrongSegmentCount { expected: usize, available: usize },
+    // Bytes were left over after the end of a nested frame.
+    TrailingBytes { offset: usize, available: usize },
+    // A bool or option tag byte other than 0 or 1.
+    InvalidTag(u8),
+    // A string or char that is not valid UTF-8.
+    InvalidUtf8,
+    // The type can only be decoded by a self-describing format.
+    Unsupported(&'static str),
+    // A message produced by a `Serialize` or `Deserialize` implementation.
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Frame(e) => write!(f, "{}", e),
+            Error::WrongLength { expected, available } => {
+                write!(f, "Expected a value of {} bytes, found {}.", expected, available)
+            }
+            Error::WrongSegmentCount { expected, available } => {
+                write!(f, "Expected a frame of {} segments, found {}.", expected, available)
+            }
+            Error::TrailingBytes { offset, available } => {
+                write!(f, "Found {} unexpected bytes after the frame at offset {}.", available, offset)
+            }
+            Error::InvalidTag(tag) => write!(f, "Expected a tag byte of 0 or 1, found {}.", tag),
+            Error::InvalidUtf8 => write!(f, "Input data contains invalid UTF-8."),
+            Error::Unsupported(what) => write!(f, "The PALS serde format does not support {}.", what),
+            Error::Message(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Frame(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PalsError> for Error {
+    fn from(err: PalsError) -> Error {
+        Error::Frame(err)
+    }
+}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize_be;
+    use ::serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message<'a> {
+        id: u32,
+        name: String,
+        #[serde(borrow)]
+        tag: &'a str,
+        #[serde(with = "bytes_as_slice")]
+        body: &'a [u8],
+        flags: (bool, char, f64),
+        parts: Vec<Option<i16>>,
+        meta: BTreeMap<String, u8>,
+        kind: Kind,
+        unit: (),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Empty,
+        Newtype(u64),
+        Tuple(i8, String),
+        Struct { a: u8, b: Vec<u8> },
+    }
+
+    // serde treats `&[u8]` as a sequence, this keeps it a plain byte string.
+    mod bytes_as_slice {
+        pub fn serialize<S: ::serde::Serializer>(bytes: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(bytes)
+        }
+
+        pub fn deserialize<'de, D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<&'de [u8], D::Error> {
+            <&[u8] as ::serde::Deserialize>::deserialize(deserializer)
+        }
+    }
+
+    #[test]
+    fn test_round_trip_struct() {
+        for kind in [Kind::Empty, Kind::Newtype(7), Kind::Tuple(-1, "x".into()), Kind::Struct { a: 1, b: vec![2, 3] }] {
+            let message = Message {
+                id: 42,
+                name: "name".into(),
+                tag: "tag",
+                body: b"body",
+                flags: (true, 'ß', 1.5),
+                parts: vec![Some(-3), None, Some(0)],
+                meta: [("a".to_string(), 1), ("b".to_string(), 2)].into_iter().collect(),
+                kind,
+                unit: (),
+            };
+
+            let encoded = to_vec(&message).unwrap();
+            let decoded: Message = from_slice(&encoded).unwrap();
+
+            assert_eq!(decoded, message);
+        }
+    }
+
+    #[test]
+    fn test_struct_layout_matches_serialize_be() {
+        #[derive(Serialize)]
+        struct Pair<'a> {
+            a: u16,
+            b: &'a str,
+        }
+
+        let encoded = to_vec(&Pair { a: 0x0102, b: "hi" }).unwrap();
+
+        assert_eq!(deserialize_be(&encoded).unwrap(), vec![vec![1, 2], b"hi".to_vec()]);
+    }
+
+    #[test]
+    fn test_borrowed_fields_point_into_input() {
+        let encoded = to_vec(&("header", 5u8)).unwrap();
+
+        let (header, _): (&str, u8) = from_slice(&encoded).unwrap();
+
+        assert_eq!(header, "header");
+        assert!(encoded.as_ptr_range().contains(&header.as_ptr()));
+    }
+
+    #[test]
+    fn test_empty_collections() {
+        let encoded = to_vec(&(Vec::<u8>::new(), String::new())).unwrap();
+
+        assert_eq!(from_slice::<(Vec<u8>, String)>(&encoded).unwrap(), (vec![], String::new()));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(from_slice::<u32>(&[1, 2, 3]), Err(Error::WrongLength { expected: 4, available: 3 }));
+        assert_eq!(from_slice::<bool>(&[2]), Err(Error::InvalidTag(2)));
+        assert_eq!(from_slice::<String>(&[0xff]), Err(Error::InvalidUtf8));
+
+        let encoded = to_vec(&(1u8, 2u8, 3u8)).unwrap();
+        assert_eq!(from_slice::<(u8, u8)>(&encoded), Err(Error::WrongSegmentCount { expected: 2, available: 3 }));
+
+        let mut encoded = to_vec(&(1u8, 2u8)).unwrap();
+        encoded.push(0);
+        assert!(matches!(from_slice::<(u8, u8)>(&encoded), Err(Error::TrailingBytes { available: 1, .. })));
+
+        assert!(matches!(from_slice::<(u8, u8)>(&[0, 0, 0]), Err(Error::Frame(PalsError::MissingTerminator { .. }))));
+    }
+}
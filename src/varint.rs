@@ -0,0 +1,197 @@
+use alloc::vec::Vec;
+
+use crate::output::Output;
+use crate::{DecodeLimits, PalsError};
+
+// The largest segment the varint layout accepts, matching the 2^62 bytes the
+// format promises for every layout.
+pub(crate) const MAX_SEGMENT_LEN: u64 = 1 << 62;
+
+// A shifted length of at most 2^62 + 1 needs 63 bits, i.e. 9 groups of 7 bits.
+pub(crate) const MAX_VARINT_LEN: usize = 9;
+
+// Append `value` to `output` as an unsigned LEB128 varint.
+pub(crate) fn write_varint(mut value: u64, output: &mut impl Output) {
+    while value >= 0x80 {
+        output.put(&[(value as u8 & 0x7f) | 0x80]);
+        value >>= 7;
+    }
+    output.put(&[value as u8]);
+}
+
+// The number of bytes `write_varint` uses for `value`.
+pub(crate) fn varint_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+// Append the varint length table for segments of the given `lengths`,
+// including the terminator, to `output`.
+pub(crate) fn write_header_varint(
+    lengths: impl Iterator<Item = usize>,
+    output: &mut impl Output,
+) -> Result<(), PalsError> {
+    let start = output.len();
+
+    for (index, len) in lengths.enumerate() {
+        if len as u64 > MAX_SEGMENT_LEN {
+            let offset = output.len() - start;
+            output.truncate(start);
+            return Err(PalsError::SegmentTooLarge {
+                offset,
+                segment: index,
+                expected: len,
+                available: MAX_SEGMENT_LEN as usize,
+            });
+        }
+        write_varint(len as u64 + 1, output);
+    }
+
+    output.put(&[0]);
+
+    Ok(())
+}
+
+// Read the varint length table at the start of `data`.
+// Returns the segment lengths and the offset at which the payload starts.
+// Only the shortest encoding of each length is accepted, so every frame has
+// exactly one valid byte representation.
+pub(crate) fn read_header_varint(data: &[u8], limits: &DecodeLimits) -> Result<(Vec<usize>, usize), PalsError> {
+    let mut lengths = Vec::new();
+    let mut total = 0;
+
+    let mut i = 0;
+
+    while i < data.len() && data[i] != 0 {
+        let start = i;
+        let mut value = 0u64;
+        let mut shift = 0;
+
+        // Collect 7 bits per byte until we reach a byte without the continuation bit.
+        loop {
+            if i == data.len() {
+                return Err(PalsError::MissingTerminator {
+                    offset: start,
+                    segment: lengths.len(),
+                    expected: i - start + 1,
+                    available: i - start,
+                });
+            }
+
+            if i - start == MAX_VARINT_LEN {
+                return Err(PalsError::InvalidVarint {
+                    offset: start,
+                    segment: lengths.len(),
+                    expected: MAX_VARINT_LEN,
+                    available: i - start + 1,
+                });
+            }
+
+            let byte = data[i];
+            value |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            i += 1;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        // A trailing zero group means the same value has a shorter encoding,
+        // and anything above 2^62 + 1 is out of range for a shifted length.
+        if varint_len(value) != i - start || value - 1 > MAX_SEGMENT_LEN {
+            return Err(PalsError::InvalidVarint {
+                offset: start,
+                segment: lengths.len(),
+                expected: varint_len(value),
+                available: i - start,
+            });
+        }
+
+        let len = usize::try_from(value - 1).map_err(|_| PalsError::LengthOverflow {
+            offset: start,
+            segment: lengths.len(),
+            expected: usize::MAX,
+            available: data.len(),
+        })?;
+
+        limits.check(start, &lengths, len, &mut total)?;
+        lengths.push(len);
+    }
+
+    if i == data.len() {
+        return Err(PalsError::MissingTerminator {
+            offset: i,
+            segment: lengths.len(),
+            expected: 1,
+            available: 0,
+        });
+    }
+
+    Ok((lengths, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_varint, serialize_varint};
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [1, 127, 128, 300, 16_383, 16_384, MAX_SEGMENT_LEN + 1] {
+            let mut output = Vec::new();
+            write_varint(value, &mut output);
+            output.push(0);
+
+            assert_eq!(output.len(), varint_len(value) + 1);
+            assert_eq!(read_header_varint(&output, &DecodeLimits::UNLIMITED).unwrap(), (vec![value as usize - 1], output.len()));
+        }
+    }
+
+    #[test]
+    fn test_serialize_deserialize_varint() {
+        let big = vec![7; 300];
+        let data: [&[u8]; 4] = [b"abc", b"", &big, b"x"];
+
+        let serialized = serialize_varint(&data).unwrap();
+
+        assert_eq!(&serialized[..6], &[4, 1, 0xad, 0x02, 2, 0]);
+        assert_eq!(deserialize_varint(&serialized).unwrap(), data);
+    }
+
+    #[test]
+    fn test_deserialize_varint_non_canonical() {
+        // 4 encoded as two bytes instead of one.
+        let data = [0x84, 0x00, 0, 1, 2, 3];
+
+        assert!(matches!(
+            deserialize_varint(&data),
+            Err(PalsError::InvalidVarint { offset: 0, segment: 0, expected: 1, available: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_varint_overlong() {
+        let data = [2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0];
+
+        assert!(matches!(deserialize_varint(&data), Err(PalsError::InvalidVarint { offset: 1, segment: 1, .. })));
+
+        // 2^62 + 2 fits into 9 bytes, but is one more than the largest allowed length.
+        let mut data = Vec::new();
+        write_varint(MAX_SEGMENT_LEN + 2, &mut data);
+        data.push(0);
+
+        assert!(matches!(deserialize_varint(&data), Err(PalsError::InvalidVarint { segment: 0, .. })));
+    }
+
+    #[test]
+    fn test_deserialize_varint_incomplete_input() {
+        let serialized = serialize_varint(&[&[1; 200]]).unwrap();
+
+        assert!(matches!(deserialize_varint(&serialized[..1]), Err(PalsError::MissingTerminator { .. })));
+        assert!(matches!(deserialize_varint(&serialized[..2]), Err(PalsError::MissingTerminator { .. })));
+        assert!(matches!(
+            deserialize_varint(&serialized[..serialized.len() - 1]),
+            Err(PalsError::TruncatedPayload { segment: 0, expected: 200, available: 199, .. })
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, IoSlice, Write};
+
+use crate::checksum::{write_trailer, Crc32c};
+use crate::{write_head, PalsConfig, PalsError, Preamble};
+
+// A frame ready for vectored output. Only the length table and the checksum
+// trailer are encoded into buffers of their own; the segments stay in the
+// caller's buffers, so `io_slices` can hand the whole frame to a single
+// `write_vectored` call without copying the payload.
+#[derive(Debug, Clone)]
+pub struct VectoredFrame<'a> {
+    header: Vec<u8>,
+    segments: &'a [&'a [u8]],
+    trailer: Vec<u8>,
+}
+
+impl<'a> VectoredFrame<'a> {
+    // Prepare a frame laid out as described by `config`.
+    pub fn with_config(config: &PalsConfig, data: &'a [&'a [u8]]) -> Result<Self, PalsError> {
+        VectoredFrame::build(config, data, false)
+    }
+
+    // Prepare the same frame as `serialize_le`.
+    pub fn le(data: &'a [&'a [u8]]) -> Result<Self, PalsError> {
+        VectoredFrame::with_config(&PalsConfig::LE, data)
+    }
+
+    // Prepare the same frame as `serialize_be`.
+    pub fn be(data: &'a [&'a [u8]]) -> Result<Self, PalsError> {
+        VectoredFrame::with_config(&PalsConfig::BE, data)
+    }
+
+    // Prepare the same frame as `serialize_varint`.
+    pub fn varint(data: &'a [&'a [u8]]) -> Result<Self, PalsError> {
+        VectoredFrame::with_config(&PalsConfig::VARINT, data)
+    }
+
+    // Encode the optional preamble, the length table and the checksum trailer.
+    pub(crate) fn build(config: &PalsConfig, data: &'a [&'a [u8]], preamble: bool) -> Result<Self, PalsError> {
+        let mut header = Vec::with_capacity(Preamble::LEN + config.header_len(data.iter().map(|i| i.len())));
+        write_head(config, data.iter().map(|i| i.len()), preamble, &mut header)?;
+
+        let frame = config.checksum.frame().then(|| {
+            let mut hasher = Crc32c::new();
+            hasher.update(&header);
+            data.iter().for_each(|i| hasher.update(i));
+            hasher
+        });
+
+        let mut trailer = Vec::with_capacity(config.checksum.trailer_len(data.len()));
+        write_trailer(config.checksum, data.iter().copied(), frame, &mut trailer);
+
+        Ok(VectoredFrame { header, segments: data, trailer })
+    }
+
+    // The encoded length table, preceded by the preamble if there is one.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    // The encoded checksum trailer, empty if the frame has no checksums.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    // The size of the whole frame in bytes.
+    pub fn frame_len(&self) -> usize {
+        self.header.len() + self.segments.iter().map(|i| i.len()).sum::<usize>() + self.trailer.len()
+    }
+
+    // The frame as a list of buffers, in order: the header, every non-empty
+    // segment and the trailer, if any.
+    pub fn io_slices(&self) -> Vec<IoSlice<'_>> {
+        let mut slices = Vec::with_capacity(self.segments.len() + 2);
+
+        slices.push(IoSlice::new(&self.header));
+        slices.extend(self.segments.iter().filter(|i| !i.is_empty()).map(|i| IoSlice::new(i)));
+        if !self.trailer.is_empty() {
+            slices.push(IoSlice::new(&self.trailer));
+        }
+
+        slices
+    }
+
+    // Write the whole frame to `writer` with as few vectored writes as it allows.
+    pub fn write_to<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        write_all_vectored(writer, &mut self.io_slices())
+    }
+}
+
+// Write all of `bufs` to `writer`, calling `write_vectored` until every byte
+// is written. Sinks may take only part of what they are offered, even part of
+// a single buffer; `bufs` is advanced past whatever was written, so its
+// contents are unspecified afterwards. Interrupted writes are retried.
+pub fn write_all_vectored<W: Write + ?Sized>(writer: &mut W, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+    // Skip leading empty buffers, so a sink that wrote nothing is not mistaken for a full one.
+    IoSlice::advance_slices(&mut bufs, 0);
+
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole frame")),
+            Ok(written) => IoSlice::advance_slices(&mut bufs, written),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_with, serialize_with_preamble, ByteOrder, Checksum, PalsWriter, Width};
+
+    // A sink that takes at most `max` bytes per call and fails every other
+    // call with `Interrupted`, to exercise the partial write handling.
+    struct Trickle {
+        output: Vec<u8>,
+        max: usize,
+        calls: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls % 2 == 0 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            let start = self.output.len();
+            for buf in bufs {
+                let take = (self.max - (self.output.len() - start)).min(buf.len());
+                self.output.extend_from_slice(&buf[..take]);
+            }
+            Ok(self.output.len() - start)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_io_slices_match_serialize() {
+        let data: [&[u8]; 4] = [b"abc", b"", &[7; 300], b"z"];
+
+        for width in [Width::U16, Width::U64, Width::Varint] {
+            for checksum in [Checksum::None, Checksum::Both] {
+                let config = PalsConfig::new(width, ByteOrder::Little).with_checksum(checksum);
+                let frame = VectoredFrame::with_config(&config, &data).unwrap();
+
+                let slices = frame.io_slices();
+                let joined: Vec<u8> = slices.iter().flat_map(|i| i.iter().copied()).collect();
+
+                assert_eq!(joined, serialize_with(&config, &data).unwrap());
+                assert_eq!(frame.frame_len(), joined.len());
+                assert_eq!(slices.len(), 4 + checksum.frame() as usize);
+                assert_eq!(slices[1].as_ptr(), data[0].as_ptr());
+            }
+        }
+
+        let data: [&[u8]; 1] = [b"abc"];
+        assert_eq!(VectoredFrame::le(&data).unwrap().header(), [4, 0]);
+        assert_eq!(VectoredFrame::be(&data).unwrap().header().len(), 16);
+        assert!(VectoredFrame::be(&data).unwrap().trailer().is_empty());
+        assert!(matches!(VectoredFrame::le(&[&[0; 300]]), Err(PalsError::SegmentTooLarge { .. })));
+    }
+
+    #[test]
+    fn test_write_all_vectored_partial_writes() {
+        let data: [&[u8]; 3] = [b"hello", b"", b"vectored world"];
+        let frame = VectoredFrame::be(&data).unwrap();
+
+        for max in [1, 3, 8, 100] {
+            let mut sink = Trickle { output: Vec::new(), max, calls: 0 };
+            frame.write_to(&mut sink).unwrap();
+            assert_eq!(sink.output, crate::serialize_be(&data).unwrap());
+        }
+
+        let mut full: &mut [u8] = &mut [0; 10];
+        let err = write_all_vectored(&mut full, &mut frame.io_slices()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+        let mut sink = Trickle { output: Vec::new(), max: 1, calls: 0 };
+        write_all_vectored(&mut sink, &mut [IoSlice::new(&[]), IoSlice::new(&[])]).unwrap();
+        assert_eq!(sink.calls, 0);
+    }
+
+    #[test]
+    fn test_writer_write_frame_vectored() {
+        let config = PalsConfig::LE.with_checksum(Checksum::Frame);
+        let mut writer = PalsWriter::with_config(Trickle { output: Vec::new(), max: 5, calls: 0 }, config).preamble(true);
+
+        writer.write_frame_vectored(&[b"ab", b"cd"]).unwrap();
+        writer.write_frame(&[b"ab", b"cd"]).unwrap();
+
+        let frame = serialize_with_preamble(&config, &[b"ab", b"cd"]).unwrap();
+        assert_eq!(writer.into_inner().output, [frame.clone(), frame].concat());
+    }
+}
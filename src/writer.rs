@@ -0,0 +1,225 @@
+use std::io::{self, Write};
+
+use crate::checksum::{write_trailer, Crc32c};
+use crate::{write_head, PalsConfig, VectoredFrame};
+
+// Writes PALS frames to any `Write` sink, such as a file or a pipe.
+// The length table is written first, followed by every segment straight from
+// the caller's buffers, so the payload is never copied into an intermediate
+// frame buffer.
+//
+// Every frame results in several small writes, so unbuffered sinks should be
+// wrapped in a `BufWriter` first, or use `write_frame_vectored` instead.
+pub struct PalsWriter<W> {
+    inner: W,
+    config: PalsConfig,
+    flush_frames: bool,
+    preamble: bool,
+    header: Vec<u8>,
+}
+
+impl<W: Write> PalsWriter<W> {
+    // Create a writer for frames laid out as described by `config`.
+    pub fn with_config(inner: W, config: PalsConfig) -> Self {
+        PalsWriter { inner, config, flush_frames: false, preamble: false, header: Vec::new() }
+    }
+
+    // Create a writer producing the same frames as `serialize_le`.
+    pub fn le(inner: W) -> Self {
+        Self::with_config(inner, PalsConfig::LE)
+    }
+
+    // Create a writer producing the same frames as `serialize_be`.
+    pub fn be(inner: W) -> Self {
+        Self::with_config(inner, PalsConfig::BE)
+    }
+
+    // Create a writer producing the same frames as `serialize_varint`.
+    pub fn varint(inner: W) -> Self {
+        Self::with_config(inner, PalsConfig::VARINT)
+    }
+
+    // Flush the underlying writer after every frame.
+    pub fn flush_frames(mut self, flush_frames: bool) -> Self {
+        self.flush_frames = flush_frames;
+        self
+    }
+
+    // Start every frame with a `Preamble`, like `serialize_with_preamble`.
+    pub fn preamble(mut self, preamble: bool) -> Self {
+        self.preamble = preamble;
+        self
+    }
+
+    // Write one frame made of the given segments.
+    // Invalid input is rejected with `io::ErrorKind::InvalidData` before
+    // anything is written; the underlying `PalsError` can be recovered from
+    // the `io::Error`.
+    pub fn write_frame(&mut self, data: &[&[u8]]) -> io::Result<()> {
+        self.header.clear();
+        write_head(&self.config, data.iter().map(|i| i.len()), self.preamble, &mut self.header)?;
+
+        self.inner.write_all(&self.header)?;
+
+        // Only hash the frame as it goes by if it needs a frame checksum.
+        let mut hasher = self.config.checksum.frame().then(Crc32c::new);
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&self.header);
+        }
+
+        for i in data {
+            self.inner.write_all(i)?;
+
+            if let Some(hasher) = &mut hasher {
+                hasher.update(i);
+            }
+        }
+
+        // Reuse the header buffer for the checksum trailer.
+        self.header.clear();
+        write_trailer(self.config.checksum, data.iter().copied(), hasher, &mut self.header);
+
+        self.inner.write_all(&self.header)?;
+
+        if self.flush_frames {
+            self.inner.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // Write one frame like `write_frame`, but hand the header, the segments
+    // and the trailer to the sink together through `write_vectored`, so a
+    // socket or file can take the whole frame in one system call.
+    pub fn write_frame_vectored(&mut self, data: &[&[u8]]) -> io::Result<()> {
+        VectoredFrame::build(&self.config, data, self.preamble)?.write_to(&mut self.inner)?;
+
+        if self.flush_frames {
+            self.inner.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    // Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    // Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    // Unwrap this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize_be, serialize_le, serialize_with_preamble, Checksum, PalsError, PalsReader};
+
+    // A writer that records how often it was flushed.
+    #[derive(Default)]
+    struct Recorder {
+        data: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_matches_serialize() {
+        let data: [&[u8]; 3] = [b"abc", b"", b"defg"];
+
+        let mut writer = PalsWriter::be(Vec::new());
+        writer.write_frame(&data).unwrap();
+        assert_eq!(writer.into_inner(), serialize_be(&data).unwrap());
+
+        let mut writer = PalsWriter::le(Vec::new());
+        writer.write_frame(&data).unwrap();
+        assert_eq!(writer.into_inner(), serialize_le(&data).unwrap());
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let mut writer = PalsWriter::be(Recorder::default()).flush_frames(true);
+        writer.write_frame(&[b"first"]).unwrap();
+        writer.write_frame(&[b"second", b"frame"]).unwrap();
+
+        let recorder = writer.into_inner();
+        assert_eq!(recorder.flushes, 2);
+
+        let frames = PalsReader::be(recorder.data.as_slice()).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(frames, vec![vec![b"first".to_vec()], vec![b"second".to_vec(), b"frame".to_vec()]]);
+    }
+
+    #[test]
+    fn test_write_with_preamble() {
+        let mut writer = PalsWriter::varint(Vec::new()).preamble(true);
+        writer.write_frame(&[b"abc"]).unwrap();
+
+        let written = writer.into_inner();
+        assert_eq!(written, serialize_with_preamble(&PalsConfig::VARINT, &[b"abc"]).unwrap());
+        assert_eq!(PalsReader::auto(written.as_slice()).next().unwrap().unwrap(), vec![b"abc".to_vec()]);
+    }
+
+    #[test]
+    fn test_write_with_checksums() {
+        let config = PalsConfig::LE.with_checksum(Checksum::Both);
+        let data: [&[u8]; 2] = [b"abc", b"defg"];
+
+        let mut writer = PalsWriter::with_config(Vec::new(), config).preamble(true);
+        writer.write_frame(&data).unwrap();
+
+        assert_eq!(writer.into_inner(), serialize_with_preamble(&config, &data).unwrap());
+    }
+
+    #[test]
+    fn test_write_rejects_invalid_frame() {
+        let mut writer = PalsWriter::le(Vec::new());
+
+        let err = writer.write_frame(&[&[0; 300]]).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<PalsError>(),
+            Some(PalsError::SegmentTooLarge { segment: 0, .. })
+        ));
+        assert!(writer.get_ref().is_empty());
+    }
+
+    #[test]
+    fn test_write_read_empty_frames() {
+        for config in [PalsConfig::LE, PalsConfig::BE, PalsConfig::VARINT] {
+            let mut writer = PalsWriter::with_config(Vec::new(), config).preamble(true);
+            writer.write_frame(&[]).unwrap();
+            writer.write_frame(&[b"x"]).unwrap();
+            writer.write_frame(&[]).unwrap();
+
+            let frames = PalsReader::auto(writer.into_inner().as_slice()).collect::<io::Result<Vec<_>>>().unwrap();
+            assert_eq!(frames, vec![vec![], vec![b"x".to_vec()], vec![]]);
+        }
+
+        let mut writer = PalsWriter::with_config(Vec::new(), PalsConfig::BE.with_reject_empty(true));
+        assert_eq!(writer.write_frame(&[]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}